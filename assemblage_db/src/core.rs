@@ -1,8 +1,9 @@
 use crate::{
     data::{BlockStyle, Child, Id, Layout, Node, Parent, Parents, Styles},
-    AsDbErrorWithContext, AsIdNotFoundErrorWithContext, Db, DbSnapshot, RestoredNode, Result, Slot,
+    tables, AsDbErrorWithContext, AsIdNotFoundErrorWithContext, Db, DbSnapshot, RestoredNode,
+    Result, Transaction, NODES, PARENTS,
 };
-use assemblage_kv::{self, storage::Storage, KvStore, Version};
+use assemblage_kv::{self, storage::Storage, Isolation, KvStore, RetryPolicy, Version};
 use async_recursion::async_recursion;
 use std::collections::{HashMap, HashSet};
 
@@ -21,6 +22,7 @@ impl<S: Storage> Db<S> {
     /// If the storage is empty, a new empty list with page layout will be
     /// automatically added as the root node of the DB.
     pub async fn open(storage: S) -> Result<Self> {
+        tables().with_context("open", "register tables")?;
         let db = Self {
             store: KvStore::open(storage).await.with_context("open", "")?,
         };
@@ -28,19 +30,36 @@ impl<S: Storage> Db<S> {
             let root = Node::List(Layout::Page, vec![]);
             let id = Id::root();
             let mut t = db.current().await;
-            t.store
-                .insert(Slot::Node as u8, &id, root)
+            NODES
+                .insert(&mut t.store, &id, root)
                 .with_context("open", "insert root node")?;
 
             let v: Parents = HashSet::new();
-            t.store
-                .insert(Slot::Parents as u8, &id, v)
+            PARENTS
+                .insert(&mut t.store, &id, v)
                 .with_context("open", "insert parents of root node")?;
             t.commit().await?;
         }
         Ok(db)
     }
 
+    /// Opens and reads a DB from storage as read-only, without ever writing to
+    /// the storage.
+    ///
+    /// Corrupted data is reported as an error instead of being truncated and
+    /// all commits and merges fail with an error for which
+    /// [`Error::is_read_only()`](crate::Error::is_read_only) returns true, see
+    /// [`KvStore::open_read_only()`]. If the storage is empty, no root node is
+    /// added.
+    pub async fn open_read_only(storage: S) -> Result<Self> {
+        tables().with_context("open_read_only", "register tables")?;
+        Ok(Self {
+            store: KvStore::open_read_only(storage)
+                .await
+                .with_context("open_read_only", "")?,
+        })
+    }
+
     /// Returns a transactional snapshot of the DB at the current point in time.
     ///
     /// A transaction is a snapshot of the DB at the point in time when the
@@ -58,6 +77,73 @@ impl<S: Storage> Db<S> {
         }
     }
 
+    /// Returns a transactional snapshot of the DB at the current point in time,
+    /// which detects conflicts with other transactions as specified by the
+    /// isolation level.
+    ///
+    /// Snapshots returned by [`Db::current()`] are serializable, see
+    /// [`Isolation`] for the alternatives.
+    pub async fn current_with(&self, isolation: Isolation) -> DbSnapshot<'_, S> {
+        DbSnapshot {
+            store: self.store.current_with(isolation).await,
+        }
+    }
+
+    /// Runs the closure in a new snapshot and commits it, retrying the whole
+    /// transaction with the default [`RetryPolicy`] if it conflicts with
+    /// another transaction.
+    ///
+    /// See [`Db::transact_with()`] for details.
+    pub async fn transact<'a, T, F>(&'a self, f: F) -> Result<T>
+    where
+        F: for<'t> FnMut(&'t mut DbSnapshot<'a, S>) -> Transaction<'t, T>,
+    {
+        self.transact_with(RetryPolicy::default(), f).await
+    }
+
+    /// Runs the closure in a new snapshot and commits it, retrying the whole
+    /// transaction as specified by the policy if it conflicts with another
+    /// transaction.
+    ///
+    /// The closure is called with a fresh snapshot for every attempt and should
+    /// thus not have any side effects outside of the snapshot. The [tx!
+    /// macro](tx) provides a less verbose alternative using `tx!(retry |db|
+    /// ...)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use assemblage_db::{data::Node, Db, Result};
+    /// use assemblage_kv::run;
+    ///
+    /// fn main() -> Result<()> {
+    ///     run!(async |storage| {
+    ///         let db = Db::open(storage).await?;
+    ///         let id = db
+    ///             .transact(|db| Box::pin(async move { db.add(Node::text("some text")).await }))
+    ///             .await?;
+    ///         assert_eq!(db.current().await.get(id).await?, Some(Node::text("some text")));
+    ///         Ok(())
+    ///     })
+    /// }
+    /// ```
+    pub async fn transact_with<'a, T, F>(&'a self, policy: RetryPolicy, mut f: F) -> Result<T>
+    where
+        F: for<'t> FnMut(&'t mut DbSnapshot<'a, S>) -> Transaction<'t, T>,
+    {
+        let mut retries = 0;
+        loop {
+            let mut snapshot = self.current().await;
+            let result = f(&mut snapshot).await;
+            match snapshot.commit_or_abort(result).await {
+                Err(e) if e.is_transaction_conflict() && policy.backoff(retries).await => {
+                    retries += 1
+                }
+                result => return result,
+            }
+        }
+    }
+
     /// Returns the name of the storage.
     pub fn name(&self) -> &str {
         self.store.name()
@@ -98,8 +184,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
     /// Returns the latest version of the node associated with the specified id
     /// or `None` if the node could not be found in the DB.
     pub async fn get(&self, id: Id) -> Result<Option<Node>> {
-        self.store
-            .get::<_, Node>(Slot::Node as u8, &id)
+        NODES
+            .get(&self.store, &id)
             .await
             .with_context("get", &format!("id {}", id))
     }
@@ -111,8 +197,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
     /// trash" (without restoring it, see [`DbSnapshot::restore`]). If the node
     /// exists in the DB, this method acts exactly like [`DbSnapshot::get`].
     pub async fn get_in_trash(&self, id: Id) -> Result<Option<Node>> {
-        self.store
-            .get_unremoved::<_, Node>(Slot::Node as u8, &id)
+        NODES
+            .get_unremoved(&self.store, &id)
             .await
             .with_context("get", &format!("id {}", id))
     }
@@ -121,8 +207,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
     /// id or an [`crate::Error::IdNotFound`] error if the id could not be found
     /// in the DB.
     pub async fn parents(&self, id: Id) -> Result<Parents> {
-        self.store
-            .get::<_, Parents>(Slot::Parents as u8, &id)
+        PARENTS
+            .get(&self.store, &id)
             .await
             .ok_or_invalid(id, "parents", "get parents by id")
     }
@@ -301,9 +387,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
     /// Returns all the versions (with their timestamps) of the specified id in
     /// the DB, ordered from earliest to latest.
     pub async fn versions(&self, id: Id) -> Result<Vec<Version>> {
-        Ok(self
-            .store
-            .versions(Slot::Node as u8, &id)
+        Ok(NODES
+            .versions(&self.store, &id)
             .await
             .with_context("versions", &format!("id {}", id))?)
     }
@@ -323,24 +408,23 @@ impl<S: Storage> DbSnapshot<'_, S> {
                     let id = self.add_unindexed(node).await?;
                     let mut parents = HashSet::new();
                     parents.insert(parent);
-                    self.store
-                        .insert(Slot::Parents as u8, &id, parents)
+                    PARENTS
+                        .insert(&mut self.store, &id, parents)
                         .with_context("add", "insert parents of eager child")?;
                     id
                 }
                 Child::Lazy(id) => {
                     self.restore_unindexed(id).await?;
                     // Only get the parents that were not removed:
-                    let mut parents = self
-                        .store
-                        .get::<_, Parents>(Slot::Parents as u8, &id)
+                    let mut parents = PARENTS
+                        .get(&self.store, &id)
                         .await
                         .with_context("add", "get parents of lazy child")?
                         .unwrap_or_else(HashSet::new);
                     if !parents.contains(&parent) {
                         parents.insert(parent);
-                        self.store
-                            .insert(Slot::Parents as u8, &id, parents)
+                        PARENTS
+                            .insert(&mut self.store, &id, parents)
                             .with_context("add", "insert parents of lazy child")?;
                     }
                     id
@@ -349,24 +433,24 @@ impl<S: Storage> DbSnapshot<'_, S> {
             lazy_children.push(Child::Lazy(id));
         }
         let node = node.with(lazy_children)?;
-        self.store
-            .insert(Slot::Node as u8, &id, node)
+        NODES
+            .insert(&mut self.store, &id, node)
             .with_context("add", "insert added node")?;
 
         let v: Parents = HashSet::new();
-        self.store
-            .insert(Slot::Parents as u8, &id, v)
+        PARENTS
+            .insert(&mut self.store, &id, v)
             .with_context("add", "insert parents of added node")?;
 
         Ok(id)
     }
 
     pub(crate) async fn swap_unindexed(&mut self, id: Id, replacement: Node) -> Result<()> {
-        let existing = self
-            .store
-            .get_unremoved::<_, Node>(Slot::Node as u8, &id)
-            .await
-            .ok_or_invalid(id, "swap_unindexed", "get existing node")?;
+        let existing = NODES.get_unremoved(&self.store, &id).await.ok_or_invalid(
+            id,
+            "swap_unindexed",
+            "get existing node",
+        )?;
 
         // if the existing node is a parent node we may need to delete the
         // obsolete parent relationship for each of the children:
@@ -395,17 +479,16 @@ impl<S: Storage> DbSnapshot<'_, S> {
                     let child_id = self.add_unindexed(node).await?;
                     let mut parents = HashSet::new();
                     parents.insert(parent);
-                    self.store
-                        .insert(Slot::Parents as u8, &child_id, parents)
+                    PARENTS
+                        .insert(&mut self.store, &child_id, parents)
                         .with_context("swap", "insert parents of eager child")?;
                     child_id
                 }
                 Child::Lazy(id) => {
                     self.restore_unindexed(id).await?;
                     // Only get the parents that were not removed:
-                    let mut parents = self
-                        .store
-                        .get::<_, Parents>(Slot::Parents as u8, &id)
+                    let mut parents = PARENTS
+                        .get(&self.store, &id)
                         .await
                         .with_context("swap", "get parents of lazy child")?
                         .unwrap_or_else(HashSet::new);
@@ -415,8 +498,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
                         }
                     }
                     parents.insert(parent);
-                    self.store
-                        .insert(Slot::Parents as u8, &id, parents)
+                    PARENTS
+                        .insert(&mut self.store, &id, parents)
                         .with_context("swap", "insert parents of lazy child")?;
                     id
                 }
@@ -442,9 +525,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
         let mut remaining_children = HashSet::new();
         let mut candidates: Vec<Id> = removed.iter().copied().collect();
         while let Some(id) = candidates.pop() {
-            let is_obsolete = self
-                .store
-                .get_unremoved::<_, Parents>(Slot::Parents as u8, &id)
+            let is_obsolete = PARENTS
+                .get_unremoved(&self.store, &id)
                 .await
                 .ok_or_invalid(id, "swap_unindexed", "get parents of obsolete node")?
                 .into_iter()
@@ -454,11 +536,11 @@ impl<S: Storage> DbSnapshot<'_, S> {
                 // check all its children, even if they were checked before, to
                 // make sure diamond dependencies are properly removed.
                 if !obsolete.contains(&id) {
-                    let obsolete_node = self
-                        .store
-                        .get_unremoved::<_, Node>(Slot::Node as u8, &id)
-                        .await
-                        .ok_or_invalid(id, "swap_unindexed", "get obsolete")?;
+                    let obsolete_node = NODES.get_unremoved(&self.store, &id).await.ok_or_invalid(
+                        id,
+                        "swap_unindexed",
+                        "get obsolete",
+                    )?;
                     for child in obsolete_node.children() {
                         candidates.push(child.id()?);
                     }
@@ -472,14 +554,14 @@ impl<S: Storage> DbSnapshot<'_, S> {
         // ...and remove these obsolete children and their subtrees from the
         // store.
         for id in obsolete.iter() {
-            self.store
-                .remove(Slot::Parents as u8, &id)
+            PARENTS
+                .remove(&mut self.store, id)
                 .with_context("swap", "remove parents of obsolete node")?;
 
             // The node contents should remain accessible if accessed directly
             // by id, so we just remove them without overwriting first.
-            self.store
-                .remove(Slot::Node as u8, &id)
+            NODES
+                .remove(&mut self.store, id)
                 .with_context("swap", "remove obsolete node")?;
         }
 
@@ -491,16 +573,15 @@ impl<S: Storage> DbSnapshot<'_, S> {
                 obsolete_parents.get(&id).map_or(true, |ps| !ps.contains(p))
                     && !obsolete.contains(&p.id)
             };
-            let parents: Parents = self
-                .store
-                .get_unremoved::<_, Parents>(Slot::Parents as u8, &id)
+            let parents: Parents = PARENTS
+                .get_unremoved(&self.store, &id)
                 .await
                 .ok_or_invalid(id, "swap_unindexed", "get parents of remaining node child")?
                 .into_iter()
                 .filter(remaining_parent)
                 .collect();
-            self.store
-                .insert(Slot::Parents as u8, &id, parents)
+            PARENTS
+                .insert(&mut self.store, &id, parents)
                 .with_context("swap", "insert parents of remaining child")?;
         }
 
@@ -511,27 +592,26 @@ impl<S: Storage> DbSnapshot<'_, S> {
         let mut candidates: Vec<Id> = removed.difference(&obsolete).copied().collect();
         while let Some(id) = candidates.pop() {
             visited.insert(id);
-            let parents = self
-                .store
-                .get_unremoved::<_, Parents>(Slot::Parents as u8, &id)
+            let parents = PARENTS
+                .get_unremoved(&self.store, &id)
                 .await
                 .ok_or_invalid(id, "swap_unindexed", "get parents of removed node")?;
             let parents_len = parents.len();
-            let remaining_parents: Vec<Parent> = parents
+            let remaining_parents: Parents = parents
                 .into_iter()
                 .filter(|p| !obsolete.contains(&p.id) && p.id != swapped_id)
                 .collect();
 
             if remaining_parents.len() != parents_len && obsolete.contains(&id) {
-                self.store
-                    .insert(Slot::Parents as u8, &id, remaining_parents)
+                PARENTS
+                    .insert(&mut self.store, &id, remaining_parents)
                     .with_context("swap", "insert remaining parents")?;
             }
-            let removed_node = self
-                .store
-                .get_unremoved::<_, Node>(Slot::Node as u8, &id)
-                .await
-                .ok_or_invalid(id, "swap_unindexed", "get removed")?;
+            let removed_node = NODES.get_unremoved(&self.store, &id).await.ok_or_invalid(
+                id,
+                "swap_unindexed",
+                "get removed",
+            )?;
             for child in removed_node.children() {
                 if !visited.contains(&child.id()?) {
                     candidates.push(child.id()?);
@@ -542,8 +622,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
         // Now that all the children and their parents are handled, we can
         // finally insert the swapped node with its children.
         let v = replacement.with(lazy_children)?;
-        self.store
-            .insert(Slot::Node as u8, &id, v)
+        NODES
+            .insert(&mut self.store, &id, v)
             .with_context("swap", "insert replacement node")?;
 
         Ok(())
@@ -551,9 +631,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
 
     #[async_recursion(?Send)]
     pub(crate) async fn restore_unindexed(&mut self, id: Id) -> Result<RestoredNode> {
-        let is_removed = self
-            .store
-            .versions(Slot::Node as u8, &id)
+        let is_removed = NODES
+            .versions(&self.store, &id)
             .await
             .map(|mut versions| versions.pop())
             .ok_or_invalid(id, "restore_unindexed", "get versions")?
@@ -562,16 +641,16 @@ impl<S: Storage> DbSnapshot<'_, S> {
             return Ok(RestoredNode::NoNeedToRestoreNode);
         }
 
-        let node = self
-            .store
-            .get_unremoved::<_, Node>(Slot::Node as u8, &id)
-            .await
-            .ok_or_invalid(id, "restore_unindexed", "get removed node")?;
-        self.store
-            .insert(Slot::Node as u8, &id, &node)
+        let node = NODES.get_unremoved(&self.store, &id).await.ok_or_invalid(
+            id,
+            "restore_unindexed",
+            "get removed node",
+        )?;
+        NODES
+            .insert(&mut self.store, &id, node.clone())
             .with_context("restore_unindexed", "insert restored node")?;
-        self.store
-            .insert(Slot::Parents as u8, &id, HashSet::<Parent>::new())
+        PARENTS
+            .insert(&mut self.store, &id, HashSet::<Parent>::new())
             .with_context("restore_unindexed", "insert empty parents")?;
 
         for (index, child) in node.children().into_iter().enumerate() {
@@ -579,15 +658,14 @@ impl<S: Storage> DbSnapshot<'_, S> {
             let id = child.id()?;
             let mut parents = match self.restore_unindexed(id).await? {
                 RestoredNode::Restored(_) => HashSet::new(),
-                RestoredNode::NoNeedToRestoreNode => self
-                    .store
-                    .get_unremoved::<_, Parents>(Slot::Parents as u8, &id)
+                RestoredNode::NoNeedToRestoreNode => PARENTS
+                    .get_unremoved(&self.store, &id)
                     .await
                     .ok_or_invalid(id, "restore_unindexed", "get parents of restored child")?,
             };
             parents.insert(restored_parent);
-            self.store
-                .insert(Slot::Parents as u8, &id, parents)
+            PARENTS
+                .insert(&mut self.store, &id, parents)
                 .with_context("restore_unindexed", "insert restored parents of child")?;
         }
         Ok(RestoredNode::Restored(node))
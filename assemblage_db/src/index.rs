@@ -1,8 +1,8 @@
 use crate::{
-    broadcast::{self, Broadcast, BroadcastId, BroadcastSubscription, OwnedBroadcast},
+    broadcast::{self, Broadcast, BroadcastId, OwnedBroadcast},
     data::{Child, Id, Layout, Node, Overlap, Parent, Parents, Styles},
     AsDbErrorWithContext, AsIdNotFoundErrorWithContext, DbSnapshot, Error, RestoredNode, Result,
-    Slot,
+    BROADCASTS_PUBLISHED, BROADCASTS_SUBSCRIBED, COUNTS, GRAMS, NODES, OVERLAPS, PARENTS,
 };
 use assemblage_kv::{
     self,
@@ -29,15 +29,14 @@ impl<S: Storage> DbSnapshot<'_, S> {
     /// broadcast will be updated by appending the contents that were modified
     /// since the last broadcast update.
     pub async fn publish_broadcast(&mut self, id: Id) -> Result<Broadcast> {
-        let existing_broadcast = self
-            .store
-            .get::<_, OwnedBroadcast>(Slot::BroadcastPublished as u8, &id)
+        let existing_broadcast = BROADCASTS_PUBLISHED
+            .get(&self.store, &id)
             .await
             .with_context("publish_broadcast", "get published broadcast")?;
         let broadcast = broadcast::push(self, id, existing_broadcast.as_ref()).await?;
         let result = (&broadcast).into();
-        self.store
-            .insert(Slot::BroadcastPublished as u8, id, broadcast)
+        BROADCASTS_PUBLISHED
+            .insert(&mut self.store, &id, broadcast)
             .with_context("publish_broadcast", "insert published broadcasts")?;
         Ok(result)
     }
@@ -45,9 +44,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
     /// Subscribes to the specified broadcast, fetching it only if no existing
     /// subscription exists and returning the number of bytes received.
     pub async fn subscribe_to_broadcast(&mut self, id: &BroadcastId) -> Result<u32> {
-        let subscription = self
-            .store
-            .get::<_, BroadcastSubscription>(Slot::BroadcastSubscribed as u8, id)
+        let subscription = BROADCASTS_SUBSCRIBED
+            .get(&self.store, id)
             .await
             .with_context("subscribe_to_broadcast", "get subscription")?;
         if subscription.is_none() {
@@ -65,9 +63,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
     /// since the last fetch. Creates a subscription for the broadcast and
     /// fetches all of its content if no existing subscription for it exists.
     pub async fn fetch_broadcast(&mut self, id: &BroadcastId) -> Result<u32> {
-        let mut subscription = self
-            .store
-            .get::<_, BroadcastSubscription>(Slot::BroadcastSubscribed as u8, &id)
+        let mut subscription = BROADCASTS_SUBSCRIBED
+            .get(&self.store, id)
             .await
             .with_context("fetch_broadcast", "get subscription")?
             .unwrap_or_default();
@@ -77,8 +74,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
             subscription.namespace = Id::new();
         }
         self.import(&bytes, subscription.namespace).await?;
-        self.store
-            .insert(Slot::BroadcastSubscribed as u8, id, subscription)
+        BROADCASTS_SUBSCRIBED
+            .insert(&mut self.store, id, subscription)
             .with_context("fetch_broadcasts", "insert subscription")?;
         Ok(bytes.len() as u32)
     }
@@ -87,15 +84,14 @@ impl<S: Storage> DbSnapshot<'_, S> {
     /// specified node.
     pub async fn list_broadcasts(&self, id: Id) -> Result<BTreeSet<Broadcast>> {
         let mut published: Vec<OwnedBroadcast> = Vec::new();
-        let keys: Vec<Id> = self
-            .store
-            .keys(Slot::BroadcastPublished as u8)
+        let keys: Vec<Id> = BROADCASTS_PUBLISHED
+            .keys(&self.store)
             .await
             .with_context("list_broadcasts", "get published broadcast keys")?;
         for id in keys {
             published.push(
-                self.store
-                    .get(Slot::BroadcastPublished as u8, &id)
+                BROADCASTS_PUBLISHED
+                    .get(&self.store, &id)
                     .await
                     .with_context("list_broadcasts", "get published broadcast")?
                     .unwrap_or_else(|| panic!("Id {} not found in the store", id)),
@@ -119,15 +115,13 @@ impl<S: Storage> DbSnapshot<'_, S> {
     /// fetching the latest version.
     pub async fn update_broadcasts(&mut self, id: Id) -> Result<()> {
         let mut published: HashMap<Id, OwnedBroadcast> = HashMap::new();
-        let keys: Vec<Id> = self
-            .store
-            .keys(Slot::BroadcastPublished as u8)
+        let keys: Vec<Id> = BROADCASTS_PUBLISHED
+            .keys(&self.store)
             .await
             .with_context("update_broadcasts", "get published broadcast keys")?;
         for id in keys {
-            let broadcast = self
-                .store
-                .get(Slot::BroadcastPublished as u8, &id)
+            let broadcast = BROADCASTS_PUBLISHED
+                .get(&self.store, &id)
                 .await
                 .with_context("update_broadcasts", "get published broadcast")?
                 .unwrap_or_else(|| panic!("Id {} could not be found in the store", id));
@@ -135,7 +129,7 @@ impl<S: Storage> DbSnapshot<'_, S> {
         }
         let descendants = self.descendants_until_links(id).await?;
         let now = timestamp_now();
-        let updated: HashMap<_, _> = {
+        let mut updated: HashMap<_, _> = {
             let relevant_broadcasts: Vec<_> = published
                 .iter()
                 .filter(|(_id, b)| {
@@ -157,13 +151,13 @@ impl<S: Storage> DbSnapshot<'_, S> {
                 .collect()
         };
         for (id, b) in published.into_iter() {
-            if let Some(broadcast) = updated.get(&id) {
-                self.store
-                    .insert(Slot::BroadcastPublished as u8, id, broadcast)
+            if let Some(broadcast) = updated.remove(&id) {
+                BROADCASTS_PUBLISHED
+                    .insert(&mut self.store, &id, broadcast)
                     .with_context("update_broadcasts", "insert updated broadcast")?;
             } else if b.expiration.is_some() && b.expiration.unwrap() <= now {
-                self.store
-                    .remove(Slot::BroadcastPublished as u8, id)
+                BROADCASTS_PUBLISHED
+                    .remove(&mut self.store, &id)
                     .with_context("update_broadcasts", "remove expired broadcast")?;
             }
         }
@@ -185,8 +179,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
 
     /// Returns all nodes with content that overlaps with the specified node.
     pub async fn overlaps(&self, id: Id) -> Result<Vec<Overlap>> {
-        self.store
-            .get(Slot::Overlaps as u8, &id)
+        OVERLAPS
+            .get(&self.store, &id)
             .await
             .ok_or_invalid(id, "overlaps", "get overlaps in store")
     }
@@ -210,9 +204,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
         let mut overlaps = Vec::new();
         let mut intersections = HashMap::new();
         for (gram, source_occurs) in source_occurs.iter() {
-            let matches = self
-                .store
-                .get::<_, HashMap<Id, Occurrences>>(Slot::Grams as u8, &gram)
+            let matches = GRAMS
+                .get(&self.store, gram)
                 .await
                 .with_context("find", "get n-grams")?;
             if let Some(matches) = matches {
@@ -226,9 +219,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
         }
         for (id, intersection) in intersections {
             let match_count = match mode {
-                SearchMode::SymmetricOverlap => self
-                    .store
-                    .get::<_, u32>(Slot::Count as u8, &id)
+                SearchMode::SymmetricOverlap => COUNTS
+                    .get(&self.store, &id)
                     .await
                     .with_context("find", "get n-gram count")?
                     .unwrap_or_else(|| panic!("No count for id {} was found in the store", id)),
@@ -245,9 +237,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
         before: &mut Index,
         after: &mut Index,
     ) -> Result<()> {
-        let mut stack: Vec<Parent> = self
-            .store
-            .get_unremoved::<_, Parents>(Slot::Parents as u8, &id)
+        let mut stack: Vec<Parent> = PARENTS
+            .get_unremoved(&self.store, &id)
             .await
             .ok_or_invalid(id, "update_parent_index", "get parents")?
             .into_iter()
@@ -268,9 +259,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
                 self.store_count(&after.blocks)?;
                 self.store_grams(&diff).await?;
             } else {
-                let parents: Vec<Parent> = self
-                    .store
-                    .get_unremoved::<_, Parents>(Slot::Parents as u8, &id)
+                let parents: Vec<Parent> = PARENTS
+                    .get_unremoved(&self.store, &id)
                     .await
                     .ok_or_invalid(id, "update_parent_index", "get parents of parent")?
                     .into_iter()
@@ -286,14 +276,14 @@ impl<S: Storage> DbSnapshot<'_, S> {
     async fn store_grams(&mut self, diff: &Diff) -> Result<()> {
         let store = &mut self.store;
         for (gram, occurrences) in diff.0.iter() {
-            let mut stored_gram = store
-                .get::<_, HashMap<Id, Occurrences>>(Slot::Grams as u8, &gram)
+            let mut stored_gram = GRAMS
+                .get(store, gram)
                 .await
                 .with_context("store_grams", "get n-grams")?
                 .unwrap_or_default();
             stored_gram.extend(occurrences);
-            store
-                .insert(Slot::Grams as u8, gram, stored_gram)
+            GRAMS
+                .insert(store, gram, stored_gram)
                 .with_context("store_grams", "insert n-grams")?;
         }
         Ok(())
@@ -324,8 +314,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
                     .into_iter()
                     .filter(|o| *o != o_rev)
                     .collect();
-                self.store
-                    .insert(Slot::Overlaps as u8, o.id, overlaps_rev)
+                OVERLAPS
+                    .insert(&mut self.store, &o.id, overlaps_rev)
                     .with_context("store_overlaps", "insert removed reverse overlaps")?;
             }
             for o in added {
@@ -333,14 +323,14 @@ impl<S: Storage> DbSnapshot<'_, S> {
                 let mut overlaps_rev = self.overlaps(o.id).await.unwrap_or_default();
                 overlaps_rev.push(o_rev);
                 overlaps_rev.sort();
-                self.store
-                    .insert(Slot::Overlaps as u8, o.id, overlaps_rev)
+                OVERLAPS
+                    .insert(&mut self.store, &o.id, overlaps_rev)
                     .with_context("store_overlaps", "insert added reverse overlaps")?;
             }
 
             after.sort();
-            self.store
-                .insert(Slot::Overlaps as u8, id, after)
+            OVERLAPS
+                .insert(&mut self.store, &id, after)
                 .with_context("store_overlaps", "insert overlaps")?;
         }
         Ok(())
@@ -348,8 +338,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
 
     fn store_count(&mut self, grams: &GramsById) -> Result<()> {
         for (id, grams) in grams.iter() {
-            self.store
-                .insert(Slot::Count as u8, id, grams.len())
+            COUNTS
+                .insert(&mut self.store, id, grams.len() as u32)
                 .with_context("store_count", "insert n-gram count")?;
         }
         Ok(())
@@ -407,11 +397,11 @@ impl<S: Storage> DbSnapshot<'_, S> {
         let ids_before: HashSet<Id> = before.all.keys().copied().collect();
         let ids_after: HashSet<Id> = after.all.keys().copied().collect();
         for removed in ids_before.difference(&ids_after) {
-            self.store
-                .remove(Slot::Count as u8, removed)
+            COUNTS
+                .remove(&mut self.store, removed)
                 .with_context("swap", "remove count of removed node")?;
-            self.store
-                .remove(Slot::Overlaps as u8, removed)
+            OVERLAPS
+                .remove(&mut self.store, removed)
                 .with_context("swap", "remove overlaps of removed node")?;
         }
         Ok(())
@@ -451,6 +441,20 @@ impl<S: Storage> DbSnapshot<'_, S> {
         self.store.commit().await.with_context("commit", "")
     }
 
+    /// Commits the transaction if the result of its body is `Ok`, otherwise
+    /// aborts it and returns the error, which is how [`Db::transact_with()`]
+    /// and the [tx! macro](crate::tx) finish every attempt.
+    #[doc(hidden)]
+    pub async fn commit_or_abort<T>(self, result: Result<T>) -> Result<T> {
+        match result {
+            Ok(ret) => self.commit().await.map(|_| ret),
+            Err(e) => {
+                self.store.abort().await.with_context("transact", "abort")?;
+                Err(e)
+            }
+        }
+    }
+
     /// Copies the node with the specified id and all of its descendants into a
     /// byte vec, returning the bytes and the ids of all exported nodes.
     pub async fn export(&self, id: Id) -> Result<(Vec<u8>, HashSet<Id>)> {
@@ -486,15 +490,15 @@ impl<S: Storage> DbSnapshot<'_, S> {
         let mut transaction = store.current().await;
         for (id, (node, parents, last_version)) in nodes.into_iter() {
             if last_version.timestamp > timestamp {
-                transaction
-                    .insert(Slot::Node as u8, &id, node)
+                NODES
+                    .insert(&mut transaction, &id, node)
                     .with_context("export_since", "insert node")?;
                 let parents: HashSet<Parent> = parents
                     .into_iter()
                     .filter(|p| ids.contains(&p.id))
                     .collect();
-                transaction
-                    .insert(Slot::Parents as u8, &id, parents)
+                PARENTS
+                    .insert(&mut transaction, &id, parents)
                     .with_context("export_since", "insert parents")?;
             }
         }
@@ -505,16 +509,16 @@ impl<S: Storage> DbSnapshot<'_, S> {
         if !ids.contains(&root_id) {
             let mut parents = HashSet::new();
             parents.insert(Parent::new(root_id, 0));
-            transaction
-                .insert(Slot::Parents as u8, &id, parents)
+            PARENTS
+                .insert(&mut transaction, &id, parents)
                 .with_context("export_since", "insert root as parent")?;
             let node = Node::list(Layout::Page, vec![id]);
-            transaction
-                .insert(Slot::Node as u8, &root_id, node)
+            NODES
+                .insert(&mut transaction, &root_id, node)
                 .with_context("export_since", "insert root as node")?;
             let parents: Parents = HashSet::new();
-            transaction
-                .insert(Slot::Parents as u8, &root_id, parents)
+            PARENTS
+                .insert(&mut transaction, &root_id, parents)
                 .with_context("export_since", "insert parents of root")?;
         }
         transaction
@@ -555,8 +559,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
             .with_context("import", "open storage")?;
         let imported = store.current().await;
         let mut before = Index::new();
-        let ids_exported: Vec<Id> = imported
-            .keys(Slot::Node as u8)
+        let ids_exported: Vec<Id> = NODES
+            .keys(&imported)
             .await
             .with_context("import", "get keys of node slot")?;
         let ids_imported: Vec<Id> = ids_exported
@@ -565,9 +569,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
             .map(|id| xor_ids(id, namespace))
             .collect();
         for id in ids_imported.iter().copied() {
-            let versions = self
-                .store
-                .versions(Slot::Node as u8, &id)
+            let versions = NODES
+                .versions(&self.store, &id)
                 .await
                 .with_context("import", "get versions of node")?;
             if !versions.is_empty() {
@@ -579,8 +582,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
             // All imported ids are XOR'ed with a randomly chosen u128 to ensure
             // that duplicate broadcasts can never overwrite each other but will
             // be mapped to unique ids.
-            let node = imported
-                .get::<_, Node>(Slot::Node as u8, &id)
+            let node = NODES
+                .get(&imported, &id)
                 .await
                 .with_context("import", "get node from imported store")?
                 .unwrap_or_else(|| panic!("Id {} not found in the store", id));
@@ -593,12 +596,12 @@ impl<S: Storage> DbSnapshot<'_, S> {
                 })
                 .collect();
             let node = node.with(children)?;
-            self.store
-                .insert(Slot::Node as u8, xor_ids(id, namespace), node)
+            NODES
+                .insert(&mut self.store, &xor_ids(id, namespace), node)
                 .with_context("import", "insert imported node")?;
 
-            let parents = imported
-                .get::<_, Parents>(Slot::Parents as u8, &id)
+            let parents = PARENTS
+                .get(&imported, &id)
                 .await
                 .with_context("import", "get parents from imported store")?
                 .unwrap_or_else(|| panic!("Parents of id {} not found in the store", id));
@@ -606,8 +609,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
                 .into_iter()
                 .map(|p| Parent::new(xor_ids(p.id, namespace), p.index))
                 .collect();
-            self.store
-                .insert(Slot::Parents as u8, xor_ids(id, namespace), parents)
+            PARENTS
+                .insert(&mut self.store, &xor_ids(id, namespace), parents)
                 .with_context("import", "insert imported parents")?;
         }
 
@@ -637,9 +640,8 @@ impl<S: Storage> DbSnapshot<'_, S> {
     /// access nodes with the id that they had in the broadcast before the
     /// import.
     pub async fn namespaced_id(&self, broadcast_id: &BroadcastId, id: Id) -> Result<Id> {
-        let subscription = self
-            .store
-            .get::<_, BroadcastSubscription>(Slot::BroadcastSubscribed as u8, broadcast_id)
+        let subscription = BROADCASTS_SUBSCRIBED
+            .get(&self.store, broadcast_id)
             .await
             .with_context("subscribe_to_broadcast", "get subscription")?;
         if let Some(subscription) = subscription {
@@ -763,7 +765,7 @@ impl Index {
 struct Diff(HashMap<u32, HashMap<Id, Occurrences>>);
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
-struct Occurrences(u32);
+pub(crate) struct Occurrences(u32);
 
 impl Diff {
     fn new(before: &GramsById, after: &GramsById) -> Self {
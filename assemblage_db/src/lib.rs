@@ -176,25 +176,49 @@
 #![deny(broken_intra_doc_links)]
 #![deny(unsafe_code)]
 
-use assemblage_kv::{self, storage::Storage, KvStore, Snapshot};
+use assemblage_kv::{
+    self,
+    storage::Storage,
+    table::{Table, TableRegistry},
+    KvStore, Snapshot,
+};
 use async_recursion::async_recursion;
-use broadcast::BroadcastId;
-use data::{BlockStyle, Child, Id, Layout, Node, Parent, SpanStyle, Styles};
-use std::collections::{BTreeSet, HashSet};
+use broadcast::{BroadcastId, BroadcastSubscription, OwnedBroadcast};
+use data::{BlockStyle, Child, Id, Layout, Node, Overlap, Parent, Parents, SpanStyle, Styles};
+use index::Occurrences;
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    future::Future,
+    pin::Pin,
+};
+
+pub use assemblage_kv::RetryPolicy;
 
 pub mod broadcast;
 mod core;
 pub mod data;
 mod index;
 
-enum Slot {
-    Node = 0,
-    Parents = 1,
-    Grams = 2,
-    Count = 3,
-    Overlaps = 4,
-    BroadcastPublished = 5,
-    BroadcastSubscribed = 6,
+const NODES: Table<Id, Node> = Table::new("nodes", 0);
+const PARENTS: Table<Id, Parents> = Table::new("parents", 1);
+const GRAMS: Table<u32, HashMap<Id, Occurrences>> = Table::new("grams", 2);
+const COUNTS: Table<Id, u32> = Table::new("counts", 3);
+const OVERLAPS: Table<Id, Vec<Overlap>> = Table::new("overlaps", 4);
+const BROADCASTS_PUBLISHED: Table<Id, OwnedBroadcast> = Table::new("published broadcasts", 5);
+const BROADCASTS_SUBSCRIBED: Table<BroadcastId, BroadcastSubscription> =
+    Table::new("subscribed broadcasts", 6);
+
+/// Registers all tables of the DB, which fails if two tables share a slot.
+fn tables() -> assemblage_kv::Result<TableRegistry> {
+    let mut tables = TableRegistry::new();
+    tables.register(&NODES)?;
+    tables.register(&PARENTS)?;
+    tables.register(&GRAMS)?;
+    tables.register(&COUNTS)?;
+    tables.register(&OVERLAPS)?;
+    tables.register(&BROADCASTS_PUBLISHED)?;
+    tables.register(&BROADCASTS_SUBSCRIBED)?;
+    Ok(tables)
 }
 
 /// The error type for DB operations.
@@ -239,6 +263,32 @@ pub enum Error {
     },
 }
 
+impl Error {
+    /// Returns true if the error was caused by a conflict between concurrent
+    /// transactions, in which case the transaction can be retried.
+    pub fn is_transaction_conflict(&self) -> bool {
+        matches!(
+            self,
+            Error::StoreError {
+                err: assemblage_kv::Error::TransactionConflict,
+                ..
+            }
+        )
+    }
+
+    /// Returns true if the error was caused by writing to a DB that was opened
+    /// as read-only.
+    pub fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            Error::StoreError {
+                err: assemblage_kv::Error::ReadOnlyStore,
+                ..
+            }
+        )
+    }
+}
+
 trait AsDbErrorWithContext<T> {
     fn with_context(self, op: &str, context: &str) -> Result<T>;
 }
@@ -311,6 +361,9 @@ impl From<Error> for wasm_bindgen::JsValue {
 /// A specialized `Result` type for DB operations.
 pub type Result<R> = std::result::Result<R, Error>;
 
+/// The future returned by a closure passed to [`Db::transact()`].
+pub type Transaction<'t, T> = Pin<Box<dyn Future<Output = Result<T>> + 't>>;
+
 /// A versioned and transactional document/graph DB.
 pub struct Db<S: Storage> {
     store: KvStore<S>,
@@ -966,7 +1019,7 @@ impl Child {
 /// # Examples
 ///
 /// ```
-/// use assemblage_db::{data::Node, tx, Db, Error, Result};
+/// use assemblage_db::{data::Node, tx, Db, Error, Result, RetryPolicy};
 /// use assemblage_kv::{run, storage};
 ///
 /// fn main() -> Result<()> {
@@ -989,14 +1042,50 @@ impl Child {
 ///             // do some other stuff here, then return the value of the block
 ///             db.add(Node::text("some text")).await?
 ///         });
+///
+///         // or retry the transaction if it conflicts with another transaction:
+///         let text4_id = tx!(retry |db| db.add(Node::text("some text")).await?);
+///
+///         // with a custom retry budget and backoff:
+///         let policy = RetryPolicy { max_retries: 3, ..RetryPolicy::default() };
+///         let text5_id = tx!(retry(policy) |db| db.add(Node::text("some text")).await?);
 ///         Ok(())
 ///     })
 /// }
 /// ```
+///
+/// The `retry` variants rerun the whole block (with a new snapshot) whenever
+/// the block or the commit fails due to a conflict with another transaction,
+/// exactly like [`Db::transact_with()`]. If the block fails with any other
+/// error, the transaction is aborted and the error is returned. The block
+/// should thus not have any side effects outside of the transaction.
 #[macro_export]
 macro_rules! tx {
+    (retry |$db:ident| $tx:expr) => {{
+        $crate::tx!(retry($crate::RetryPolicy::default()) | $db | $tx)
+    }};
+
+    (retry($policy:expr) |$db:ident| $tx:expr) => {{
+        let policy: $crate::RetryPolicy = $policy;
+        let db_to_retry = &$db;
+        let mut retries = 0;
+        loop {
+            let mut $db = db_to_retry.current().await;
+            // errors of the block are returned from the async block, so that
+            // conflicts are retried just like conflicts of the commit:
+            let result = async { Ok::<_, $crate::Error>($tx) }.await;
+            match $db.commit_or_abort(result).await {
+                Err(e) if e.is_transaction_conflict() && policy.backoff(retries).await => {
+                    retries += 1;
+                }
+                result => break result?,
+            }
+        }
+    }};
+
     (|$db:ident| $tx:expr) => {{
-        use assemblage_db::{Db, DbSnapshot};
+        #[allow(unused_imports)]
+        use $crate::{Db, DbSnapshot};
         let mut $db = $db.current().await;
         let ret = $tx;
         $db.commit().await?;
@@ -1004,7 +1093,8 @@ macro_rules! tx {
     }};
 
     (|$db:ident| -> Result<$r:ty, $e:ty> $tx:block) => {{
-        use assemblage_db::{Db, DbSnapshot};
+        #[allow(unused_imports)]
+        use $crate::{Db, DbSnapshot};
         let mut $db = $db.current().await;
         let ret = $tx;
         $db.commit().await?;
@@ -1,6 +1,6 @@
 use assemblage_db::{
     data::{Child, Layout, Node, Parent, SpanStyle, Styles},
-    tx, Db, Result,
+    tx, Db, Error, Result, RetryPolicy,
 };
 use assemblage_kv::{storage, storage::Storage, test};
 use std::collections::HashSet;
@@ -202,3 +202,97 @@ test! {
         });
     }
 }
+
+test! {
+    async fn retry_conflicting_transactions(storage) -> Result<()> {
+        let db = Db::open(storage).await?;
+        let list = tx!(|db| db.add(Node::list(Layout::Page, vec![Node::text("foo")])).await?);
+
+        let other_db = &db;
+        let mut attempts = 0;
+        tx!(retry |db| {
+            attempts += 1;
+            db.update(list, |children| children.push(Node::text("bar").into())).await?;
+            if attempts == 1 {
+                tx!(|other_db| {
+                    other_db.update(list, |children| children.push(Node::text("baz").into())).await?
+                });
+            }
+        });
+        assert_eq!(attempts, 2);
+
+        let texts = db
+            .transact(|db| {
+                Box::pin(async move {
+                    let node = db.get(list).await?.unwrap();
+                    let mut texts = Vec::new();
+                    for child in node.children() {
+                        texts.push(child.of(&*db).await?.str()?.to_string());
+                    }
+                    Ok(texts)
+                })
+            })
+            .await?;
+        assert_eq!(texts, vec!["foo", "baz", "bar"]);
+    }
+}
+
+test! {
+    async fn retry_conflicts_raised_inside_transactions(storage) -> Result<()> {
+        let db = Db::open(storage).await?;
+        let mut attempts = 0;
+        let id = tx!(retry(RetryPolicy::default()) |db| {
+            attempts += 1;
+            let id = db.add(Node::text("foo")).await?;
+            if attempts == 1 {
+                return Err(Error::StoreError {
+                    err: assemblage_kv::Error::TransactionConflict,
+                    operation: "get".to_string(),
+                    context: "simulated conflict".to_string(),
+                });
+            }
+            id
+        });
+        assert_eq!(attempts, 2);
+        assert_eq!(db.current().await.get(id).await?, Some(Node::text("foo")));
+
+        let result: Result<()> = async {
+            tx!(retry |db| {
+                attempts += 1;
+                let id = db.add(Node::text("bar")).await?;
+                if attempts > 0 {
+                    return Err(Error::IdNotFound {
+                        id,
+                        operation: "get".to_string(),
+                        context: "failed on purpose".to_string(),
+                    });
+                }
+            });
+            Ok(())
+        }
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts, 3);
+    }
+}
+
+test! {
+    async fn open_read_only(storage) -> Result<()> {
+        let store_name = String::from(storage.name());
+        let db = Db::open(storage).await?;
+        let text = tx!(|db| db.add(Node::text("foo")).await?);
+        drop(db);
+
+        let storage = storage::open(&store_name).await?;
+        let len = storage.len();
+        let mut db = Db::open_read_only(storage).await?;
+        let node = db.current().await.get(text).await?.unwrap();
+        assert_eq!(node.str()?, "foo");
+
+        let mut t = db.current().await;
+        assert!(t.add(Node::text("bar")).await.unwrap_err().is_read_only());
+        assert!(t.commit().await.unwrap_err().is_read_only());
+        assert!(db.merge().await.unwrap_err().is_read_only());
+        assert_eq!(db.size().await?, len);
+    }
+}
@@ -0,0 +1,122 @@
+//! Consistent backups of a store that do not block other transactions, see
+//! [`KvStore::backup_to()`].
+use crate::{
+    codec::Codec, copy_retained, hint_name, storage::Storage, timestamp::timestamp_now, Batch,
+    BlobVersion, Entry, KvStore, MergePolicy, Result, MAX_CHUNK_SIZE,
+};
+use std::cmp::min;
+
+impl<S: Storage, C: Codec> KvStore<S, C> {
+    /// Copies a consistent snapshot of the store to the target storage, without
+    /// blocking other transactions for the whole duration of the backup.
+    ///
+    /// The backup contains all transactions up to the last commit at the time
+    /// the backup was started (including all old versions and removed values)
+    /// and can be opened as a store of its own. Any existing contents of the
+    /// target are replaced. Since the target can be any kind of storage, a
+    /// backup can also be used to copy a store across storage backends, for
+    /// example from a web storage to a memory storage.
+    pub async fn backup_to<T: Storage>(&self, target: &mut T) -> Result<()> {
+        self.backup_to_with(target, BackupPolicy::default()).await
+    }
+
+    /// Copies a consistent snapshot of the store to the target storage, but
+    /// only copies the versions that should be included according to the
+    /// specified policy, see [`KvStore::backup_to()`] and [`BackupPolicy`].
+    ///
+    /// Like [`KvStore::merge_incrementally()`], the storage is only locked
+    /// while a chunk of the store is being copied, so that other transactions
+    /// can read and commit in between. Merges of the store wait until the
+    /// backup is done.
+    pub async fn backup_to_with<T: Storage>(
+        &self,
+        target: &mut T,
+        policy: BackupPolicy,
+    ) -> Result<()> {
+        let _merging = self.merge_lock.lock().await;
+        let (end, retained) = {
+            let mut storage = self.storage.lock().await;
+            let end = match *self.latest_commit_offset.lock().await {
+                Some(offset) => offset + Entry::read_from(&mut storage, offset).await?.len() as u64,
+                None => 0,
+            };
+            let retained = if policy.merge.is_none() && policy.keep_versions_newer_than.is_none() {
+                None
+            } else {
+                let now = timestamp_now();
+                let offsets = self.offsets.lock().await;
+                Some(
+                    offsets
+                        .values()
+                        .flat_map(|versions| policy.retained(versions, now))
+                        .collect(),
+                )
+            };
+            (end, retained)
+        };
+
+        target.truncate(0).await?;
+        target.purge_sibling(&hint_name(target.name())).await?;
+        let cipher = self.cipher.as_ref();
+        let mut batch = Batch::new(0);
+        let mut offset = 0;
+        while offset < end {
+            let mut storage = self.storage.lock().await;
+            let step = MAX_CHUNK_SIZE as u64;
+            let buf = match &retained {
+                Some(retained) => {
+                    let copied = copy_retained(
+                        &mut storage,
+                        offset,
+                        end,
+                        retained,
+                        &mut batch,
+                        step,
+                        cipher,
+                    );
+                    offset = copied.await?;
+                    batch.take()
+                }
+                None => {
+                    let bytes = min(end - offset, step);
+                    offset += bytes;
+                    storage.read(offset - bytes, bytes as u32).await?
+                }
+            };
+            drop(storage);
+            target.write(&buf).await?;
+        }
+        target.flush().await?;
+        Ok(())
+    }
+}
+
+/// A policy that decides which versions are copied when backing up a store,
+/// see [`KvStore::backup_to_with()`].
+///
+/// The default policy copies all versions as they are, so that the backup is
+/// an exact copy of the store.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct BackupPolicy {
+    /// Merges the backup using the specified policy, so that only the versions
+    /// retained by the merge are copied.
+    pub merge: Option<MergePolicy>,
+    /// Only copies versions with a timestamp (in milliseconds since the Unix
+    /// epoch) after this cutoff, for example to create an incremental backup
+    /// of all changes since the last backup.
+    pub keep_versions_newer_than: Option<u64>,
+}
+
+impl BackupPolicy {
+    fn retained(&self, versions: &[BlobVersion], now: u64) -> Vec<u64> {
+        let versions: Vec<BlobVersion> = versions
+            .iter()
+            .filter(|v| !matches!(self.keep_versions_newer_than, Some(t) if v.timestamp <= t))
+            .copied()
+            .collect();
+        match self.merge {
+            Some(policy) => policy.retained(&versions, now),
+            None => versions.iter().map(|v| v.offset).collect(),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! A bounded cache of the values read from storage, which is shared by all
+//! snapshots of a store.
+use std::collections::{BTreeMap, HashMap};
+
+/// The approximate number of bytes needed to keep track of a cached value,
+/// which is added to the length of the value so that removed and empty values
+/// are not free to cache.
+const BYTES_PER_VALUE: usize = 64;
+
+/// The generation of the store and the offset of the entry of a value.
+type Key = (u64, u64);
+
+/// Caches values by the generation of the store and the offset of their entry
+/// and evicts the least recently used values once the size of all cached
+/// values exceeds the capacity (in bytes).
+///
+/// Caching by offset is safe because the committed version at an offset never
+/// changes, unless the store is merged or compacted, which either starts a new
+/// generation or clears the cache.
+#[derive(Debug)]
+pub(crate) struct ValueCache {
+    capacity: usize,
+    size: usize,
+    tick: u64,
+    values: HashMap<Key, (Option<Vec<u8>>, u64)>,
+    recently_used: BTreeMap<u64, Key>,
+}
+
+impl ValueCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            size: 0,
+            tick: 0,
+            values: HashMap::new(),
+            recently_used: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the cached value and marks it as the most recently used value.
+    pub(crate) fn get(&mut self, generation: u64, offset: u64) -> Option<Option<Vec<u8>>> {
+        let key = (generation, offset);
+        let (value, used) = self.values.get_mut(&key)?;
+        self.recently_used.remove(used);
+        self.tick += 1;
+        *used = self.tick;
+        self.recently_used.insert(self.tick, key);
+        Some(value.clone())
+    }
+
+    /// Caches the value, evicting the least recently used values if the cache
+    /// is full. Values larger than the capacity are never cached.
+    pub(crate) fn insert(&mut self, generation: u64, offset: u64, value: Option<Vec<u8>>) {
+        let key = (generation, offset);
+        let size = size_of(&value);
+        if size > self.capacity || self.values.contains_key(&key) {
+            return;
+        }
+        self.tick += 1;
+        self.size += size;
+        self.values.insert(key, (value, self.tick));
+        self.recently_used.insert(self.tick, key);
+        while self.size > self.capacity {
+            let (_, key) = match self.recently_used.pop_first() {
+                Some(lru) => lru,
+                None => break,
+            };
+            if let Some((value, _)) = self.values.remove(&key) {
+                self.size -= size_of(&value);
+            }
+        }
+    }
+
+    /// Removes all values from the cache.
+    pub(crate) fn clear(&mut self) {
+        self.size = 0;
+        self.values.clear();
+        self.recently_used.clear();
+    }
+}
+
+fn size_of(value: &Option<Vec<u8>>) -> usize {
+    value.as_ref().map_or(0, Vec::len) + BYTES_PER_VALUE
+}
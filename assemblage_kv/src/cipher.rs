@@ -0,0 +1,84 @@
+//! Authenticated encryption of entries, only available with the "encryption"
+//! feature.
+use crate::{Error, Result};
+
+#[cfg(feature = "encryption")]
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+
+#[cfg(feature = "encryption")]
+const BYTES_NONCE: usize = 24;
+
+/// Encrypts and decrypts keys and values using XChaCha20-Poly1305, with a
+/// random nonce stored in front of each ciphertext.
+///
+/// The associated data is authenticated but not encrypted (and not stored as
+/// part of the ciphertext), decryption fails if it does not match the
+/// associated data that was used for encryption.
+#[cfg(feature = "encryption")]
+pub(crate) struct Cipher(XChaCha20Poly1305);
+
+/// Placeholder for the cipher if the "encryption" feature is disabled, cannot
+/// be constructed.
+#[cfg(not(feature = "encryption"))]
+pub(crate) enum Cipher {}
+
+#[cfg(feature = "encryption")]
+impl Cipher {
+    pub(crate) fn new(key: &[u8; 32]) -> Self {
+        Self(XChaCha20Poly1305::new(key.into()))
+    }
+
+    pub(crate) fn encrypt(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: plaintext,
+            aad,
+        };
+        let ciphertext = self
+            .0
+            .encrypt(&nonce, payload)
+            .map_err(|_| Error::InvalidEntryError {
+                reason: "Unable to encrypt entry".to_string(),
+            })?;
+        let mut bytes = nonce.to_vec();
+        bytes.extend(ciphertext);
+        Ok(bytes)
+    }
+
+    pub(crate) fn decrypt(&self, bytes: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        if bytes.len() < BYTES_NONCE {
+            return Err(Error::InvalidEncryptionKey);
+        }
+        let (nonce, ciphertext) = bytes.split_at(BYTES_NONCE);
+        let payload = Payload {
+            msg: ciphertext,
+            aad,
+        };
+        self.0
+            .decrypt(XNonce::from_slice(nonce), payload)
+            .map_err(|_| Error::InvalidEncryptionKey)
+    }
+}
+
+#[cfg(not(feature = "encryption"))]
+impl Cipher {
+    pub(crate) fn encrypt(&self, _plaintext: &[u8], _aad: &[u8]) -> Result<Vec<u8>> {
+        match *self {}
+    }
+
+    pub(crate) fn decrypt(&self, _bytes: &[u8], _aad: &[u8]) -> Result<Vec<u8>> {
+        match *self {}
+    }
+}
+
+/// Decrypts the bytes if a cipher is available, otherwise fails with
+/// [`Error::InvalidEncryptionKey`].
+pub(crate) fn decrypt(cipher: Option<&Cipher>, bytes: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    match cipher {
+        Some(cipher) => cipher.decrypt(bytes, aad),
+        None => Err(Error::InvalidEncryptionKey),
+    }
+}
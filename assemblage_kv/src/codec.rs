@@ -0,0 +1,89 @@
+//! Codecs that serialize the keys and values of a store to bytes.
+//!
+//! A store serializes all keys and values using its [`Codec`], which is
+//! [`MessagePack`] by default. Other codecs can be used by calling
+//! [`crate::KvStore::with_codec()`] after opening the store. Since the entries
+//! of a store do not record which codec was used to write them, a store must
+//! always be read using the same codec that was used to write it.
+//!
+//! The byte order of the serialized keys determines the order of
+//! [`crate::Snapshot::keys()`] and the results of
+//! [`crate::Snapshot::range()`] and [`crate::Snapshot::scan_prefix()`].
+//! Range scans additionally require that the serialized keys of a slot are
+//! self-delimiting, i.e. that no serialized key is a proper prefix of another
+//! key of the same type, which holds for all of the codecs in this module.
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Serializes and deserializes the keys and values of a store.
+///
+/// Errors are returned as a human-readable reason and are reported as an
+/// [`crate::Error::InvalidKeyError`] or [`crate::Error::InvalidValueError`] by
+/// the store.
+pub trait Codec {
+    /// Serializes a key or value to bytes.
+    fn encode<T: Serialize + ?Sized>(&self, t: &T) -> Result<Vec<u8>, String>;
+
+    /// Deserializes a key or value from bytes.
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, String>;
+}
+
+/// The default codec, which serializes keys and values to
+/// [MessagePack](https://msgpack.org/) using `rmp-serde`.
+///
+/// The serialized representation matches the natural ordering for unsigned
+/// ints and for strings of equal length.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePack;
+
+impl Codec for MessagePack {
+    fn encode<T: Serialize + ?Sized>(&self, t: &T) -> Result<Vec<u8>, String> {
+        rmp_serde::encode::to_vec(t).map_err(|e| format!("Unable to serialize: {}", e))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, String> {
+        rmp_serde::decode::from_read(bytes).map_err(|e| format!("{}", e))
+    }
+}
+
+/// A codec that serializes keys and values using `bincode`, which is usually
+/// faster and more compact than MessagePack for fixed-size types.
+///
+/// Ints are serialized in little-endian byte order, so that their serialized
+/// representation does not match their natural ordering.
+#[cfg(feature = "bincode")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bincode;
+
+#[cfg(feature = "bincode")]
+impl Codec for Bincode {
+    fn encode<T: Serialize + ?Sized>(&self, t: &T) -> Result<Vec<u8>, String> {
+        bincode::serialize(t).map_err(|e| format!("Unable to serialize: {}", e))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, String> {
+        bincode::deserialize(bytes).map_err(|e| format!("{}", e))
+    }
+}
+
+/// A codec that serializes keys and values to [CBOR](https://cbor.io/) using
+/// `ciborium`.
+///
+/// Like MessagePack, the serialized representation matches the natural
+/// ordering for unsigned ints and for strings of equal length.
+#[cfg(feature = "cbor")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cbor;
+
+#[cfg(feature = "cbor")]
+impl Codec for Cbor {
+    fn encode<T: Serialize + ?Sized>(&self, t: &T) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(t, &mut bytes)
+            .map_err(|e| format!("Unable to serialize: {}", e))?;
+        Ok(bytes)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, String> {
+        ciborium::de::from_reader(bytes).map_err(|e| format!("{}", e))
+    }
+}
@@ -0,0 +1,318 @@
+//! Verification and repair of corrupt stores, see [`KvStore::verify()`] and
+//! [`KvStore::repair()`].
+use crate::{
+    hint_name, multi::recover_intent, storage, storage::Storage, u32_from_bytes, Entry, Error,
+    KvStore, ReadAt, Result, BYTES_CRC, MAX_CHUNK_SIZE,
+};
+use crc32fast::Hasher;
+use std::{cmp::min, collections::HashMap};
+
+const MAX_SCANS_PER_COMMIT: usize = 16;
+
+impl<S: Storage> KvStore<S> {
+    /// Checks the integrity of the store in the storage without opening it.
+    ///
+    /// All transactions are read and their checksums are compared, but keys
+    /// and values are never decrypted or deserialized, so that encrypted
+    /// stores can be verified without a key. Unlike [`KvStore::open()`],
+    /// verification does not stop at the first corrupt transaction and instead
+    /// looks for valid transactions after it, see [`IntegrityReport`].
+    pub async fn verify(storage: &mut S) -> Result<IntegrityReport> {
+        Ok(scan_store(storage).await?.report)
+    }
+
+    /// Repairs the store in the storage using the specified strategy and
+    /// returns the report of the store before the repair, see
+    /// [`KvStore::verify()`].
+    ///
+    /// Repairing a store removes all corrupt transactions and all orphaned
+    /// writes that were never committed, as well as the hint of the store.
+    /// Does nothing if no corruption was found.
+    ///
+    /// An interrupted multi-store transaction of the store is recovered before
+    /// the store is checked, just like when the store is opened, see
+    /// [`Snapshot::commit_all()`](crate::Snapshot::commit_all).
+    pub async fn repair(storage: &mut S, strategy: RepairStrategy) -> Result<IntegrityReport> {
+        let name = String::from(storage.name());
+        recover_intent(&name, storage).await?;
+        let scan = scan_store(storage).await?;
+        if scan.report.is_ok() {
+            return Ok(scan.report);
+        }
+        storage.purge_sibling(&hint_name(storage.name())).await?;
+        match strategy {
+            RepairStrategy::Truncate => {
+                storage.truncate(scan.valid_end).await?;
+            }
+            RepairStrategy::Salvage => {
+                storage.flush().await?;
+                storage.start_merge().await?;
+                // valid ranges always end after a transaction and are written
+                // at once (but read in chunks), so that writes never split a
+                // transaction:
+                for (start, end) in scan.valid_ranges {
+                    let mut buf = Vec::with_capacity((end - start) as usize);
+                    let mut offset = start;
+                    while offset < end {
+                        let bytes = min(end - offset, MAX_CHUNK_SIZE as u64);
+                        buf.extend(storage.read(offset, bytes as u32).await?);
+                        offset += bytes;
+                    }
+                    storage.write(&buf).await?;
+                }
+                storage.flush().await?;
+                storage.stop_merge().await?;
+            }
+        }
+        storage.flush().await?;
+        Ok(scan.report)
+    }
+}
+
+/// The result of checking the integrity of a store, see [`KvStore::verify()`].
+///
+/// A transaction is valid if all of its entries can be read and its commit
+/// matches the checksum of its entries. Kv entries at the end of the store that
+/// are not followed by a commit are orphaned writes of a transaction that was
+/// interrupted before it could be committed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// The number of entries in valid transactions, including their commits.
+    pub entries: u64,
+    /// The number of valid transactions.
+    pub commits: u64,
+    /// The offsets of all corrupt transactions.
+    pub corrupt_offsets: Vec<u64>,
+    /// The number of orphaned kv entries at the end of the store.
+    pub orphaned_entries: u64,
+    /// The number of bytes that are not part of any valid transaction and are
+    /// dropped by any repair.
+    pub bytes_dropped: u64,
+    /// The number of bytes after the last valid transaction before the first
+    /// corruption, which are dropped by [`RepairStrategy::Truncate`].
+    pub bytes_truncated: u64,
+}
+
+impl IntegrityReport {
+    /// Returns `true` if the store consists only of valid transactions.
+    pub fn is_ok(&self) -> bool {
+        self.bytes_dropped == 0
+    }
+}
+
+/// The strategy used to repair a corrupt store, see [`KvStore::repair()`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum RepairStrategy {
+    /// Truncates the store at the first corrupt transaction and discards all
+    /// transactions after it, which is what [`KvStore::open()`] does.
+    #[default]
+    Truncate,
+    /// Removes only the corrupt transactions and keeps all valid transactions
+    /// after them.
+    Salvage,
+}
+
+pub(crate) enum ScannedTransaction {
+    Valid { end: u64, entries: u64 },
+    Corrupt { end: Option<u64> },
+    Uncommitted { entries: u64 },
+}
+
+/// Reads the transaction starting at the offset and checks its CRC, without
+/// decrypting or deserializing any of its entries.
+pub(crate) async fn scan_transaction<R: ReadAt>(
+    storage: &mut R,
+    mut offset: u64,
+    len: u64,
+) -> Result<ScannedTransaction> {
+    let mut crc = Hasher::new();
+    let mut entries = 0;
+    while offset < len {
+        let entry = match Entry::read_from(storage, offset).await {
+            Ok(entry) if offset + entry.len() as u64 <= len => entry,
+            Ok(_) | Err(Error::StorageError(storage::Error::OffsetError { .. })) => break,
+            Err(Error::StorageError(e)) => return Err(Error::StorageError(e)),
+            Err(_) => return Ok(ScannedTransaction::Corrupt { end: None }),
+        };
+        offset += entry.len() as u64;
+        if entry.is_transaction_commit() {
+            entry.update_crc(&mut crc);
+            return Ok(if crc.finalize() == entry.crc()? {
+                ScannedTransaction::Valid {
+                    end: offset,
+                    entries: entries + 1,
+                }
+            } else {
+                ScannedTransaction::Corrupt { end: Some(offset) }
+            });
+        } else if !entry.is_transaction() {
+            entry.update_crc(&mut crc);
+            entries += 1;
+        }
+    }
+    Ok(ScannedTransaction::Uncommitted { entries })
+}
+
+/// Reads only the header and sizes of the entry at the offset and returns the
+/// length of the entry and whether it is a commit, or `None` if the entry is
+/// invalid, is an empty transaction entry (which is never written, but would
+/// otherwise match any zero byte) or does not end before `len`.
+async fn entry_length_at<R: ReadAt>(
+    storage: &mut R,
+    offset: u64,
+    len: u64,
+) -> Result<Option<(u64, bool)>> {
+    let bytes = match storage.read_at(offset, 1 + 3 + 6).await {
+        Ok(bytes) => bytes,
+        Err(Error::StorageError(storage::Error::OffsetError { .. })) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let header = match bytes.first() {
+        Some(header) => *header,
+        None => return Ok(None),
+    };
+    let bytes_val_size = (header & 0b111) as usize;
+    let bytes_key_size = ((header & 0b11000) >> 3) as usize;
+    let bytes_sizes = bytes_key_size + bytes_val_size;
+    if bytes_key_size > 3 || bytes_val_size > 4 || bytes.len() < 1 + bytes_sizes {
+        return Ok(None);
+    }
+    let key_size = u32_from_bytes(&bytes[1..1 + bytes_key_size])? as u64;
+    let val_size = u32_from_bytes(&bytes[1 + bytes_key_size..1 + bytes_sizes])? as u64;
+    if key_size >= 1 << 24 || val_size > 1 << 24 {
+        return Ok(None);
+    }
+    let is_commit = bytes_key_size == 0 && val_size > 0;
+    if bytes_key_size == 0 && !is_commit {
+        return Ok(None);
+    }
+    let bytes_crc = if is_commit { BYTES_CRC as u64 } else { 0 };
+    let length = 1 + bytes_sizes as u64 + key_size + val_size + bytes_crc;
+    Ok(if offset + length <= len {
+        Some((length, is_commit))
+    } else {
+        None
+    })
+}
+
+/// Follows the entries starting at the offset by reading only their headers and
+/// returns the end of the first commit, or `None` if an invalid entry comes
+/// first. The results are memoized for all visited offsets, so that each entry
+/// header is read only once while looking for the next valid transaction.
+async fn commit_end<R: ReadAt>(
+    storage: &mut R,
+    offset: u64,
+    len: u64,
+    commit_ends: &mut HashMap<u64, Option<u64>>,
+) -> Result<Option<u64>> {
+    let mut visited = Vec::new();
+    let mut offset = offset;
+    let end = loop {
+        if let Some(end) = commit_ends.get(&offset) {
+            break *end;
+        }
+        visited.push(offset);
+        match entry_length_at(storage, offset, len).await? {
+            Some((length, true)) => break Some(offset + length),
+            Some((length, false)) => offset += length,
+            None => break None,
+        }
+    };
+    for offset in visited {
+        commit_ends.insert(offset, end);
+    }
+    Ok(end)
+}
+
+struct StoreScan {
+    report: IntegrityReport,
+    valid_ranges: Vec<(u64, u64)>,
+    valid_end: u64,
+}
+
+/// Reads all transactions of the store, skipping over corrupt transactions by
+/// looking for the next offset where a valid transaction starts.
+async fn scan_store<S: Storage>(storage: &mut S) -> Result<StoreScan> {
+    let len = storage.len();
+    let mut report = IntegrityReport::default();
+    let mut valid_ranges: Vec<(u64, u64)> = Vec::new();
+    let mut offset = 0;
+    while offset < len {
+        let (end, orphaned_entries) = match scan_transaction(storage, offset, len).await? {
+            ScannedTransaction::Valid { end, entries } => {
+                report.entries += entries;
+                report.commits += 1;
+                match valid_ranges.last_mut() {
+                    Some((start, range_end))
+                        if *range_end == offset && end - *start <= MAX_CHUNK_SIZE as u64 =>
+                    {
+                        *range_end = end
+                    }
+                    _ => valid_ranges.push((offset, end)),
+                }
+                offset = end;
+                continue;
+            }
+            ScannedTransaction::Corrupt { end } => (end, None),
+            ScannedTransaction::Uncommitted { entries } => (None, Some(entries)),
+        };
+        let mut next = None;
+        if let Some(end) = end {
+            if let ScannedTransaction::Valid { .. } = scan_transaction(storage, end, len).await? {
+                next = Some(end);
+            }
+        }
+        // the size of an entry might be corrupt, so the next transaction could
+        // start at any offset, but only offsets whose entry headers lead to a
+        // commit are fully scanned (and only a limited number per commit):
+        let mut commit_ends = HashMap::new();
+        let mut scans = HashMap::new();
+        let mut candidate = offset + 1;
+        while next.is_none() && candidate < len {
+            if let Some(end) = commit_end(storage, candidate, len, &mut commit_ends).await? {
+                let attempts = scans.entry(end).or_insert(0);
+                if *attempts < MAX_SCANS_PER_COMMIT {
+                    *attempts += 1;
+                    if let ScannedTransaction::Valid { .. } =
+                        scan_transaction(storage, candidate, len).await?
+                    {
+                        next = Some(candidate);
+                    }
+                }
+            }
+            candidate += 1;
+            if commit_ends.len() > 1 << 16 {
+                commit_ends.retain(|offset, _| *offset >= candidate);
+            }
+        }
+        match (next, orphaned_entries) {
+            (None, Some(entries)) => {
+                report.orphaned_entries = entries;
+                break;
+            }
+            (None, None) => {
+                report.corrupt_offsets.push(offset);
+                break;
+            }
+            (Some(next), _) => {
+                report.corrupt_offsets.push(offset);
+                offset = next;
+            }
+        }
+    }
+    let mut valid_end = 0;
+    for (start, end) in valid_ranges.iter() {
+        if *start != valid_end {
+            break;
+        }
+        valid_end = *end;
+    }
+    let bytes_valid: u64 = valid_ranges.iter().map(|(start, end)| end - start).sum();
+    report.bytes_dropped = len - bytes_valid;
+    report.bytes_truncated = len - valid_end;
+    Ok(StoreScan {
+        report,
+        valid_ranges,
+        valid_end,
+    })
+}
@@ -5,17 +5,34 @@
 //! [Bitcask](https://riak.com/assets/bitcask-intro.pdf). Writes of new or
 //! changed values never overwrite old entries, but are simply appended to the
 //! end of the storage. Old values are kept at earlier offsets in the storage
-//! and remain accessible. An in-memory hash table tracks the storage offsets of
-//! all keys and allows efficient reads directly from the relevant portions of
-//! the storage. A store can be merged, which discards old versions and builds a
-//! more compact representation containing only the latest value of each key.
+//! and remain accessible. An in-memory key directory (ordered by the serialized
+//! keys) tracks the storage offsets of all keys and allows efficient reads
+//! directly from the relevant portions of the storage, as well as ordered range
+//! scans over the keys of a slot. A store can be merged, which discards old
+//! versions and builds a more compact representation containing only the latest
+//! value of each key. After a merge, the key directory is persisted as a
+//! "hint" next to the store, so that re-opening a store only needs to read the
+//! entries that were appended since the hint was written.
 //!
 //! ## Features
 //!
 //!   - _simple_: log-structured hash architecture, with all keys in memory
 //!   - _fully versioned:_ old values remain accessible until merged
 //!   - _transactional:_ all reads and writes happen only in isolated transactions
+//!   - _atomic across stores:_ transactions of several stores can be committed
+//!     together, so that either all or none of them are persisted
+//!   - _cached:_ values are cached in a bounded LRU cache shared by all transactions
 //!   - _storage-agnostic:_ supports files on native and IndexedDB on wasm
+//!   - _compressed (optional):_ values are compressed using LZ4 if the `compression`
+//!     feature is enabled, older uncompressed entries remain readable
+//!   - _encrypted (optional):_ keys and values are encrypted and authenticated with a
+//!     user-supplied key if the `encryption` feature is enabled
+//!   - _memory-mapped (optional):_ file storages are read through a memory map (on
+//!     native) if the `mmap` feature is enabled, so that snapshots read without locking
+//!   - _segmented (optional):_ stores can be split into segment files (on native), so
+//!     that segments with many old versions can be compacted independently
+//!   - _pluggable codecs:_ keys and values are serialized as MessagePack by default,
+//!     bincode and CBOR are available with the `bincode` and `cbor` features
 //!
 //! ## Example
 //!
@@ -80,18 +97,66 @@
 #![deny(broken_intra_doc_links)]
 #![deny(unsafe_code)]
 
-use crate::{storage::Storage, timestamp::timestamp_now_monotonic};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::storage::SegmentedStorage;
+use crate::{
+    cache::ValueCache,
+    cipher::Cipher,
+    codec::{Codec, MessagePack},
+    storage::{Storage, View},
+    timestamp::{sleep, timestamp_after, timestamp_now, timestamp_now_monotonic},
+};
+use async_trait::async_trait;
 use crc32fast::Hasher;
-use log::warn;
-use serde::{de::DeserializeOwned, Serialize};
-use std::{cmp::max, collections::HashMap, mem};
-use tokio::sync::{Mutex, MutexGuard};
-
+use futures_core::Stream;
+use log::{debug, info, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    cmp::{max, min},
+    collections::{BTreeMap, HashMap, HashSet},
+    future::Future,
+    mem,
+    ops::{Bound, Range, RangeBounds},
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll},
+};
+use tokio::sync::{
+    mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    Mutex, MutexGuard,
+};
+
+mod backup;
+mod cache;
+mod cipher;
+pub mod codec;
+mod integrity;
+mod multi;
+mod replication;
+mod savepoint;
+mod stats;
 pub mod storage;
+pub mod table;
 pub mod timestamp;
 
+pub use backup::BackupPolicy;
+pub use integrity::{IntegrityReport, RepairStrategy};
+pub use replication::TransactionFrame;
+pub use savepoint::Savepoint;
+pub use stats::{SlotStats, Stats};
+
+use multi::recover_intent;
+use savepoint::{next_snapshot_id, UndoLog};
+use stats::Counters;
+
 const BYTES_TIMESTAMP_FULL: usize = 6;
 const BYTES_CRC: usize = 4;
+const FLAG_COMPRESSED: u8 = 0b100000;
+const FLAG_ENCRYPTED: u8 = 0b1000000;
+const FLAG_CHUNKED: u8 = 0b10000000;
+const MAX_CHUNK_SIZE: usize = 1 << 23;
+const DEFAULT_CACHE_CAPACITY: usize = 1 << 23;
+const BYTES_COMMIT: u64 = (1 + 1 + BYTES_TIMESTAMP_FULL + BYTES_CRC) as u64;
 
 /// The error type for store operations.
 #[derive(Debug)]
@@ -135,6 +200,52 @@ pub enum Error {
     StorageLockError,
     /// The transaction has read a value that has since been overwritten.
     TransactionConflict,
+    /// The snapshot is read-only and cannot be used to write to the store.
+    ReadOnlySnapshot,
+    /// The store was opened as read-only and cannot be written to or merged.
+    ReadOnlyStore,
+    /// An encrypted entry could not be decrypted, because the store was opened
+    /// without a key or with the wrong key.
+    InvalidEncryptionKey,
+    /// The transaction frame does not continue the log of the store.
+    InvalidFrameError {
+        /// The reason why the frame was invalid.
+        reason: String,
+    },
+    /// Two different tables claimed the same slot, see
+    /// [`table::TableRegistry`].
+    DuplicateSlot {
+        /// The slot that was claimed by both tables.
+        slot: u8,
+        /// The name of the table that was registered first.
+        registered: String,
+        /// The name of the table that was rejected.
+        rejected: String,
+    },
+    /// A table with the same name but a different slot, key type or value type
+    /// has already been registered, see [`table::TableRegistry`].
+    ConflictingTable {
+        /// The name of the table.
+        name: String,
+        /// How the rejected table differs from the registered table.
+        reason: String,
+    },
+    /// Two snapshots of the same store were committed together, see
+    /// [`Snapshot::commit_all()`].
+    DuplicateStore {
+        /// The name of the store.
+        name: String,
+    },
+    /// A multi-store commit failed after its decision to commit was stored and
+    /// left the store out of sync with its storage, so that the store must be
+    /// reopened, see [`Snapshot::commit_all()`].
+    InconsistentStore,
+    /// The savepoint has already been released or rolled back, see
+    /// [`Snapshot::savepoint()`].
+    UnknownSavepoint,
+    /// The savepoint was created by a different snapshot, see
+    /// [`Snapshot::savepoint()`].
+    ForeignSavepoint,
 }
 
 /// A specialized `Result` type for store operations.
@@ -163,15 +274,26 @@ impl<'a> From<storage::Error> for Error {
 /// unremoved version if the value was "moved to the trash").
 ///
 ///   - Keys/values are `Serialize`/`Deserialize` and are
-///     serialized/deserialized to/from [MessagePack](https://msgpack.org/)
-///     using `rmp-serde`.
+///     serialized/deserialized using the [`Codec`] of the store, which is
+///     [MessagePack](https://msgpack.org/) by default, see
+///     [`KvStore::with_codec()`].
 ///   - Keys are not read/written as-is, but always associated with a "slot".
 ///     These slots act as the different "indexes" of a store.
-pub struct KvStore<S: Storage> {
+pub struct KvStore<S: Storage, C: Codec = MessagePack> {
     name: String,
     storage: Mutex<S>,
-    offsets: Mutex<HashMap<Vec<u8>, Vec<BlobVersion>>>,
+    offsets: Mutex<BTreeMap<Vec<u8>, Vec<BlobVersion>>>,
     latest_timestamp: Mutex<u64>,
+    latest_commit_offset: Mutex<Option<u64>>,
+    generation: Mutex<u64>,
+    merge_lock: Mutex<()>,
+    cipher: Option<Cipher>,
+    codec: C,
+    subscribers: Mutex<Vec<Subscriber>>,
+    counters: Mutex<Counters>,
+    cache: Mutex<ValueCache>,
+    is_read_only: bool,
+    is_inconsistent: AtomicBool,
 }
 
 impl<S: Storage> KvStore<S> {
@@ -179,26 +301,155 @@ impl<S: Storage> KvStore<S> {
     ///
     /// If no store exists at the storage location, a new store will be
     /// initialized. Otherwise, the store will be read and checked for corrupted
-    /// data. In case of corruption, everything after the corrupted offset will
-    /// be truncated and later writes will overwrite the corrupted entries.
-    /// After the initial read, a hash table of all the keys in the store and
-    /// their storage offsets is kept in memory.
+    /// data. In case of corruption, everything after the last valid transaction
+    /// before the corrupted offset will be truncated (as well as any writes of
+    /// a transaction that was interrupted before it could be committed) and
+    /// later writes will overwrite the corrupted entries. Use
+    /// [`KvStore::repair()`] before opening the store to keep valid
+    /// transactions after the corruption instead.
+    /// After the initial read, an ordered directory of all the keys in the
+    /// store and their storage offsets is kept in memory.
+    ///
+    /// If a valid hint (see [`KvStore::write_hint()`]) exists for the store,
+    /// the key directory is read from the hint and only the entries appended
+    /// after the hint was written need to be read and checked. Missing or stale
+    /// hints are ignored and the whole store is read instead.
     pub async fn open(storage: S) -> Result<Self> {
-        let mut store = Self {
+        Self::open_with(storage, None, false).await
+    }
+
+    /// Opens and reads a store from storage as read-only, without ever writing
+    /// to the storage.
+    ///
+    /// Unlike [`KvStore::open()`], corrupted data is not truncated, instead
+    /// opening the store fails with an [`Error::CorruptDataError`] at the
+    /// offset of the first corrupt transaction. Snapshots of a read-only store
+    /// can be read as usual, but all writes, commits and merges fail with an
+    /// [`Error::ReadOnlyStore`]. This makes it possible to safely inspect a
+    /// store or to serve reads from a replica of a store.
+    pub async fn open_read_only(storage: S) -> Result<Self> {
+        Self::open_with(storage, None, true).await
+    }
+
+    /// Opens and reads a store from storage, encrypting all new keys and values
+    /// with the specified 256-bit key.
+    ///
+    /// Keys and values are encrypted and authenticated using
+    /// XChaCha20-Poly1305, but the sizes of entries, the boundaries of
+    /// transactions and their timestamps are stored unencrypted. Unencrypted
+    /// entries written by an unencrypted store remain readable and are
+    /// encrypted during the next merge. Opening a store with the wrong key (or
+    /// opening an encrypted store using [`KvStore::open()`]) fails with an
+    /// [`Error::InvalidEncryptionKey`] instead of truncating the store.
+    #[cfg(feature = "encryption")]
+    pub async fn open_encrypted(storage: S, key: &[u8; 32]) -> Result<Self> {
+        Self::open_with(storage, Some(Cipher::new(key)), false).await
+    }
+
+    /// Opens and reads an encrypted store from storage as read-only, see
+    /// [`KvStore::open_encrypted()`] and [`KvStore::open_read_only()`].
+    #[cfg(feature = "encryption")]
+    pub async fn open_encrypted_read_only(storage: S, key: &[u8; 32]) -> Result<Self> {
+        Self::open_with(storage, Some(Cipher::new(key)), true).await
+    }
+
+    async fn open_with(storage: S, cipher: Option<Cipher>, is_read_only: bool) -> Result<Self> {
+        let store = Self {
             name: String::from(storage.name()),
             storage: Mutex::new(storage),
-            offsets: Mutex::new(HashMap::new()),
+            offsets: Mutex::new(BTreeMap::new()),
             latest_timestamp: Mutex::new(0),
+            latest_commit_offset: Mutex::new(None),
+            generation: Mutex::new(0),
+            merge_lock: Mutex::new(()),
+            cipher,
+            codec: MessagePack,
+            subscribers: Mutex::new(Vec::new()),
+            counters: Mutex::new(Counters::default()),
+            cache: Mutex::new(ValueCache::new(DEFAULT_CACHE_CAPACITY)),
+            is_read_only,
+            is_inconsistent: AtomicBool::new(false),
         };
-        init_store(&mut store).await?;
+        let started = timestamp_now();
+        init_store(&store).await?;
+        info!(
+            "Opened store {} ({} bytes) in {}ms",
+            store.name,
+            store.len().await,
+            timestamp_now().saturating_sub(started)
+        );
         Ok(store)
     }
+}
+
+impl<S: Storage, C: Codec> KvStore<S, C> {
+    /// Consumes the store to return the same store using the specified codec
+    /// to serialize and deserialize its keys and values, see [`codec`].
+    ///
+    /// The codec is not recorded in the storage, so a store must always be
+    /// opened with the codec that was used to write it. Keys and values written
+    /// using a different codec will fail to deserialize or, worse, deserialize
+    /// to different keys and values.
+    pub fn with_codec<D: Codec>(self, codec: D) -> KvStore<S, D> {
+        KvStore {
+            name: self.name,
+            storage: self.storage,
+            offsets: self.offsets,
+            latest_timestamp: self.latest_timestamp,
+            latest_commit_offset: self.latest_commit_offset,
+            generation: self.generation,
+            merge_lock: self.merge_lock,
+            cipher: self.cipher,
+            codec,
+            subscribers: self.subscribers,
+            counters: self.counters,
+            cache: self.cache,
+            is_read_only: self.is_read_only,
+            is_inconsistent: self.is_inconsistent,
+        }
+    }
+
+    /// Sets the capacity (in bytes) of the value cache that is shared by all
+    /// snapshots of the store, discarding all cached values.
+    ///
+    /// Each snapshot caches the values it has read, but only for as long as
+    /// the snapshot is alive. Values that are read from storage are
+    /// additionally cached by the store, so that values that are frequently
+    /// read (such as the root of a tree) are read from storage only once
+    /// instead of once per snapshot. Once the cache is full, the least
+    /// recently used values are evicted. The default capacity is 8 MiB, a
+    /// capacity of 0 disables the cache.
+    pub fn with_cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache = Mutex::new(ValueCache::new(capacity));
+        self
+    }
+
+    /// Returns the codec that is used to serialize keys and values.
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
 
     /// Returns the (file-)name of the storage.
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    /// Returns `true` if the store was opened as read-only, see
+    /// [`KvStore::open_read_only()`].
+    pub fn is_read_only(&self) -> bool {
+        self.is_read_only
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.is_read_only {
+            Err(Error::ReadOnlyStore)
+        } else if self.is_inconsistent.load(Ordering::SeqCst) {
+            Err(Error::InconsistentStore)
+        } else {
+            Ok(())
+        }
+    }
+
     /// Consumes the store to return its underlying storage.
     pub fn into_storage(self) -> Result<S> {
         Ok(self.storage.into_inner())
@@ -214,69 +465,1003 @@ impl<S: Storage> KvStore<S> {
         self.len().await == 0
     }
 
+    /// Subscribes to all future commits of the store, see [`Subscription`].
+    pub async fn subscribe(&self) -> Subscription {
+        self.subscribe_with(None).await
+    }
+
+    /// Subscribes to all future commits of the store that write to at least
+    /// one of the specified slots, see [`Subscription`].
+    ///
+    /// Only the changes of the specified slots are included in the commits
+    /// that are received by the subscription.
+    pub async fn subscribe_to(&self, slots: &[u8]) -> Subscription {
+        self.subscribe_with(Some(slots.to_vec())).await
+    }
+
+    async fn subscribe_with(&self, slots: Option<Vec<u8>>) -> Subscription {
+        let (sender, receiver) = unbounded_channel();
+        self.subscribers
+            .lock()
+            .await
+            .push(Subscriber { slots, sender });
+        Subscription { receiver }
+    }
+
+    async fn notify(&self, timestamp: u64, changes: &[Change]) {
+        let mut subscribers = self.subscribers.lock().await;
+        // dropped subscriptions are removed when the next commit is sent:
+        subscribers.retain(|subscriber| {
+            let is_subscribed = |c: &&Change| match &subscriber.slots {
+                Some(slots) => slots.contains(&c.slot),
+                None => true,
+            };
+            let changes: Vec<Change> = changes.iter().filter(is_subscribed).cloned().collect();
+            changes.is_empty()
+                || subscriber
+                    .sender
+                    .send(Commit { timestamp, changes })
+                    .is_ok()
+        });
+    }
+
+    /// Runs the closure in a new snapshot and commits it, retrying the whole
+    /// transaction if it fails with an [`Error::TransactionConflict`].
+    ///
+    /// Equivalent to [`KvStore::transact_with()`] using the default
+    /// [`RetryPolicy`].
+    pub async fn transact<'a, T, F>(&'a self, f: F) -> Result<T>
+    where
+        F: for<'t> FnMut(&'t mut Snapshot<'a, S, C>) -> Transaction<'t, T>,
+    {
+        self.transact_with(RetryPolicy::default(), f).await
+    }
+
+    /// Runs the closure in a new snapshot and commits it, retrying the whole
+    /// transaction as specified by the policy if either the closure or the
+    /// commit fail with an [`Error::TransactionConflict`].
+    ///
+    /// The closure is called with a fresh snapshot for every attempt and should
+    /// thus not have any side effects outside of the snapshot. All other errors
+    /// are returned immediately, as is the last conflict once the retries of
+    /// the policy are exhausted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use assemblage_kv::{run, KvStore, Result, RetryPolicy};
+    ///
+    /// fn main() -> Result<()> {
+    ///     run!(async |storage| {
+    ///         let store = KvStore::open(storage).await?;
+    ///         let slot = 0;
+    ///
+    ///         let policy = RetryPolicy { max_retries: 3, ..RetryPolicy::default() };
+    ///         let count = store
+    ///             .transact_with(policy, |t| {
+    ///                 Box::pin(async move {
+    ///                     let count = t.get::<_, u32>(slot, &"count").await?.unwrap_or(0) + 1;
+    ///                     t.insert(slot, "count", count)?;
+    ///                     Ok(count)
+    ///                 })
+    ///             })
+    ///             .await?;
+    ///         assert_eq!(count, 1);
+    ///         Ok(())
+    ///     })
+    /// }
+    /// ```
+    pub async fn transact_with<'a, T, F>(&'a self, policy: RetryPolicy, mut f: F) -> Result<T>
+    where
+        F: for<'t> FnMut(&'t mut Snapshot<'a, S, C>) -> Transaction<'t, T>,
+    {
+        let mut retries = 0;
+        loop {
+            let mut snapshot = self.current().await;
+            let result = match f(&mut snapshot).await {
+                Ok(ret) => snapshot.commit().await.map(|_| ret),
+                Err(e) => {
+                    snapshot.abort().await?;
+                    Err(e)
+                }
+            };
+            match result {
+                Err(Error::TransactionConflict) if policy.backoff(retries).await => retries += 1,
+                result => return result,
+            }
+        }
+    }
+
     /// Creates a transactional read-write snapshot of the store at the current
     /// point in time, see [`Snapshot`].
-    pub async fn current(&self) -> Snapshot<'_, S> {
+    ///
+    /// Equivalent to [`KvStore::current_with()`] using
+    /// [`Isolation::Serializable`].
+    pub async fn current(&self) -> Snapshot<'_, S, C> {
+        self.current_with(Isolation::Serializable).await
+    }
+
+    /// Creates a transactional read-write snapshot of the store at the current
+    /// point in time, which detects conflicts with other transactions as
+    /// specified by the isolation level, see [`Snapshot`].
+    pub async fn current_with(&self, isolation: Isolation) -> Snapshot<'_, S, C> {
         let latest_timestamp = *self.latest_timestamp.lock().await;
-        let latest_offset = self.storage.lock().await.len();
+        let (latest_offset, generation) = self.offset_and_generation().await;
         let snapshot_timestamp = timestamp_now_monotonic(latest_timestamp);
+        let boundary = if snapshot_timestamp == latest_timestamp {
+            SnapshotBoundary::Offset(latest_offset)
+        } else {
+            SnapshotBoundary::Timestamp(latest_timestamp)
+        };
         Snapshot {
             store: self,
             snapshot_timestamp,
             latest_timestamp,
             latest_offset,
+            generation,
+            boundary,
+            is_read_only: false,
+            isolation,
+            transaction_entries: HashMap::new(),
+            id: next_snapshot_id(),
+            savepoints: Vec::new(),
+            next_savepoint: 0,
             cached_entries: Mutex::new(HashMap::new()),
+            read_keys: Mutex::new(HashSet::new()),
+            read_ranges: Mutex::new(Vec::new()),
+            view: Mutex::new(None),
+        }
+    }
+
+    /// Creates a read-only snapshot of the store as it was at the specified
+    /// point in time (in milliseconds since the Unix epoch), see [`Snapshot`].
+    ///
+    /// All reads of the snapshot behave as if the store had been frozen at the
+    /// specified timestamp, ignoring all versions committed after it. Since
+    /// merges discard old versions, only the history since the last merge is
+    /// available. Inserts, removes and commits of the snapshot fail with
+    /// [`Error::ReadOnlySnapshot`].
+    pub async fn at(&self, timestamp: u64) -> Snapshot<'_, S, C> {
+        let latest_timestamp = self
+            .offsets
+            .lock()
+            .await
+            .values()
+            .filter_map(|versions| versions.iter().rev().find(|v| v.timestamp <= timestamp))
+            .map(|v| v.timestamp)
+            .max()
+            .unwrap_or(0);
+        let (latest_offset, generation) = self.offset_and_generation().await;
+        Snapshot {
+            store: self,
+            snapshot_timestamp: timestamp,
+            latest_timestamp,
+            latest_offset,
+            generation,
+            boundary: SnapshotBoundary::Timestamp(timestamp),
+            is_read_only: true,
+            isolation: Isolation::Serializable,
             transaction_entries: HashMap::new(),
+            id: next_snapshot_id(),
+            savepoints: Vec::new(),
+            next_savepoint: 0,
+            cached_entries: Mutex::new(HashMap::new()),
+            read_keys: Mutex::new(HashSet::new()),
+            read_ranges: Mutex::new(Vec::new()),
+            view: Mutex::new(None),
         }
     }
 
+    async fn offset_and_generation(&self) -> (u64, u64) {
+        let storage = self.storage.lock().await;
+        (storage.len(), *self.generation.lock().await)
+    }
+
     /// Merges and compacts the store by removing old versions.
     ///
     /// Merging a store reclaims space by removing all versions that were
     /// superseded by newer writes to the same key. As a side effect, a merge
     /// "empties the trash" and ensures that removed values cannot be read and
     /// restored anymore.
+    ///
+    /// After the merge, a hint of the merged store is written, see
+    /// [`KvStore::write_hint()`].
     pub async fn merge(&mut self) -> Result<()> {
+        self.merge_with(MergePolicy::default()).await
+    }
+
+    /// Merges and compacts the store, but keeps all old versions and removed
+    /// values that should be retained according to the specified policy.
+    ///
+    /// The latest version of each key is always kept, the policy only decides
+    /// which _additional_ versions survive the merge, see [`MergePolicy`].
+    /// Merging with the default policy is equivalent to [`KvStore::merge()`].
+    pub async fn merge_with(&mut self, policy: MergePolicy) -> Result<()> {
+        self.check_writable()?;
         {
-            self.storage.lock().await.flush().await?;
-            self.storage.lock().await.start_merge().await?;
+            let mut storage = self.storage.lock().await;
+            let (started, len_before) = (timestamp_now(), storage.len());
+            storage.purge_sibling(&hint_name(&self.name)).await?;
+            storage.flush().await?;
+            storage.start_merge().await?;
 
-            let mut crc = Hasher::new();
+            let retained = self.retained_offsets(policy).await;
+            let mut batch = Batch::new(0);
+            let len = storage.len();
+            let cipher = self.cipher.as_ref();
             let mut offset = 0;
+            while offset < len {
+                let step = MAX_CHUNK_SIZE as u64;
+                let copied = copy_retained(
+                    &mut storage,
+                    offset,
+                    len,
+                    &retained,
+                    &mut batch,
+                    step,
+                    cipher,
+                );
+                offset = copied.await?;
+                storage.write(&batch.take()).await?;
+            }
+
+            storage.flush().await?;
+            storage.stop_merge().await?;
+            storage.flush().await?;
+            load_store(self, &mut storage).await?;
+            log_merge(&self.name, started, len_before, storage.len());
+        }
+        self.write_hint().await
+    }
+
+    /// Merges and compacts the store incrementally, without blocking other
+    /// transactions for the whole duration of the merge.
+    ///
+    /// Unlike [`KvStore::merge_with()`], this method only needs a shared
+    /// reference to the store and can run concurrently with other transactions.
+    /// All entries that exist when the merge starts are copied in chunks of
+    /// (roughly) `chunk_size` bytes and the storage is only locked while a
+    /// chunk is being copied, so that other transactions can read and commit
+    /// in between. Transactions that are committed during the merge are copied
+    /// as-is at the end of the merge, when the store atomically switches over
+    /// to the merged storage.
+    ///
+    /// Snapshots that were created before the switch cannot be used after the
+    /// merge, all of their reads and commits will fail with an
+    /// [`Error::TransactionConflict`] and must be rerun.
+    ///
+    /// If the merge fails before the switch, the merge is aborted and the store
+    /// continues to use its unmerged storage.
+    pub async fn merge_incrementally(&self, policy: MergePolicy, chunk_size: u64) -> Result<()> {
+        self.check_writable()?;
+        let _merging = self.merge_lock.lock().await;
+        let started = timestamp_now();
+        let (end, retained) = {
+            let mut storage = self.storage.lock().await;
+            storage.flush().await?;
+            storage.start_merge().await?;
+            if let Err(e) = storage.pause_merge().await {
+                storage.abort_merge().await?;
+                return Err(e.into());
+            }
+            (storage.len(), self.retained_offsets(policy).await)
+        };
+
+        let copied = self.copy_incrementally(end, &retained, chunk_size).await;
+        let mut storage = self.storage.lock().await;
+        let switched = match copied {
+            Ok(batch) => self.switch_to_merged(&mut storage, end, batch).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = switched {
+            // the storage must not keep forking writes into a stale merge:
+            storage.abort_merge().await?;
+            return Err(e);
+        }
+        storage.flush().await?;
+        load_store(self, &mut storage).await?;
+        log_merge(&self.name, started, end, storage.len());
+        drop(storage);
+        self.write_hint().await
+    }
+
+    async fn copy_incrementally(
+        &self,
+        end: u64,
+        retained: &HashSet<u64>,
+        chunk_size: u64,
+    ) -> Result<Batch> {
+        let cipher = self.cipher.as_ref();
+        let mut batch = Batch::new(0);
+        let mut offset = 0;
+        while offset < end {
             let mut storage = self.storage.lock().await;
-            while offset < storage.len() {
-                let mut entry = Entry::read_from(&mut storage, offset).await?;
-                let entry_length = entry.len() as u64;
-                let offsets = &mut (*self.offsets.lock().await);
-
-                // all kv writes have Some(key), all transactions have None
-                if let Some(k) = entry.key.as_ref() {
-                    if offset == offsets[k].last().unwrap().offset {
-                        entry.update_crc(&mut crc);
-                        entry.write_to(&mut storage).await?;
+            let copied = copy_retained(
+                &mut storage,
+                offset,
+                end,
+                retained,
+                &mut batch,
+                chunk_size,
+                cipher,
+            );
+            offset = copied.await?;
+            storage.resume_merge().await?;
+            let written = storage.write(&batch.take()).await;
+            storage.pause_merge().await?;
+            written?;
+        }
+        Ok(batch)
+    }
+
+    async fn switch_to_merged(
+        &self,
+        storage: &mut MutexGuard<'_, S>,
+        end: u64,
+        mut batch: Batch,
+    ) -> Result<()> {
+        storage.purge_sibling(&hint_name(&self.name)).await?;
+        let committed_during_merge: HashSet<u64> = self
+            .offsets
+            .lock()
+            .await
+            .values()
+            .flatten()
+            .map(|v| v.offset)
+            .filter(|offset| *offset >= end)
+            .collect();
+        let len = storage.len();
+        let copied = copy_retained(
+            storage,
+            end,
+            len,
+            &committed_during_merge,
+            &mut batch,
+            u64::MAX,
+            self.cipher.as_ref(),
+        );
+        copied.await?;
+        storage.resume_merge().await?;
+        storage.write(&batch.take()).await?;
+        storage.flush().await?;
+        storage.stop_merge().await?;
+        *self.generation.lock().await += 1;
+        Ok(())
+    }
+
+    async fn retained_offsets(&self, policy: MergePolicy) -> HashSet<u64> {
+        let now = timestamp_now();
+        self.offsets
+            .lock()
+            .await
+            .values()
+            .flat_map(|versions| policy.retained(versions, now))
+            .collect()
+    }
+
+    /// Persists the in-memory key directory as a hint next to the store.
+    ///
+    /// The hint is stored in a separate storage (named after the store, see
+    /// [`hint_name()`]) and allows [`KvStore::open()`] to skip reading all
+    /// entries up to the last commit at the time the hint was written. Hints
+    /// are written automatically after a merge, but can also be written
+    /// explicitly, for example when shutting down cleanly.
+    pub async fn write_hint(&self) -> Result<()> {
+        self.check_writable()?;
+        let mut storage = self.storage.lock().await;
+        storage.flush().await?;
+        let name = hint_name(&self.name);
+        storage.purge_sibling(&name).await?;
+        let commit_offset = match *self.latest_commit_offset.lock().await {
+            Some(offset) => offset,
+            None => return Ok(()),
+        };
+        let commit = Entry::read_from(&mut storage, commit_offset).await?;
+        let hint = Hint {
+            offset: commit_offset + commit.len() as u64,
+            commit_offset,
+            commit_crc: commit.crc()?,
+            latest_timestamp: *self.latest_timestamp.lock().await,
+            keys: self.offsets.lock().await.clone(),
+        };
+        let mut payload =
+            rmp_serde::encode::to_vec(&hint).map_err(|e| Error::InvalidEntryError {
+                reason: format!("Unable to serialize hint: {}", e),
+            })?;
+        if let Some(cipher) = &self.cipher {
+            payload = cipher.encrypt(&payload, &[])?;
+        }
+        let mut hint_storage = storage.open_sibling(&name).await?;
+        hint_storage
+            .write(&crc32fast::hash(&payload).to_le_bytes())
+            .await?;
+        hint_storage.write(&payload).await?;
+        hint_storage.flush().await?;
+        Ok(())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<C: Codec> KvStore<SegmentedStorage, C> {
+    /// Compacts the segments of the store that contain mostly old versions.
+    ///
+    /// Equivalent to [`KvStore::compact_with()`] using the default
+    /// [`CompactionPolicy`].
+    pub async fn compact(&mut self) -> Result<()> {
+        self.compact_with(CompactionPolicy::default()).await
+    }
+
+    /// Compacts the store segment by segment, merging only the segments in
+    /// which the share of versions that would be discarded by a merge reaches
+    /// the threshold of the policy, see [`SegmentedStorage`].
+    ///
+    /// Unlike [`KvStore::merge_with()`], which rewrites the whole store, a
+    /// compaction only rewrites the segments that contain enough garbage and
+    /// leaves all other segments untouched, including the last segment (which
+    /// new transactions are appended to). Each compacted segment keeps only the
+    /// versions retained by the merge policy of the compaction policy. Removed
+    /// keys are purged completely (including their removal) if all of their
+    /// versions are part of compacted segments and no version other than the
+    /// removal is retained by the merge policy, otherwise the removal is kept
+    /// so that the older versions remain removed.
+    ///
+    /// After the compaction, a hint of the compacted store is written, see
+    /// [`KvStore::write_hint()`].
+    pub async fn compact_with(&mut self, policy: CompactionPolicy) -> Result<()> {
+        self.check_writable()?;
+        {
+            let mut storage = self.storage.lock().await;
+            let (started, len_before) = (timestamp_now(), storage.len());
+            storage.purge_sibling(&hint_name(&self.name)).await?;
+            storage.flush().await?;
+            let mut offsets = self.offsets.lock().await;
+            let mut latest_commit_offset = self.latest_commit_offset.lock().await;
+            self.cache.lock().await.clear();
+
+            let ranges = storage.segment_ranges();
+            let segment_of = |offset: u64| ranges.partition_point(|r| r.end <= offset);
+            let now = timestamp_now();
+            let mut retained: HashSet<u64> = offsets
+                .values()
+                .flat_map(|versions| policy.merge.retained(versions, now))
+                .collect();
+            let mut versions = vec![0; ranges.len()];
+            let mut garbage = vec![0; ranges.len()];
+            for key_versions in offsets.values() {
+                // a removal is garbage if all older versions of its key are:
+                let is_purgeable = |v: &BlobVersion| !retained.contains(&v.offset);
+                let is_removal_purgeable = match key_versions.split_last() {
+                    Some((latest, older)) => latest.is_removed && older.iter().all(is_purgeable),
+                    None => false,
+                };
+                for (i, v) in key_versions.iter().enumerate() {
+                    let is_latest = i == key_versions.len() - 1;
+                    versions[segment_of(v.offset)] += 1;
+                    if is_purgeable(v) || (is_latest && is_removal_purgeable) {
+                        garbage[segment_of(v.offset)] += 1;
                     }
-                } else if entry.is_transaction_commit() {
-                    entry.update_crc(&mut crc);
-                    let crc_merged = crc.finalize();
-                    let crc_original = entry.crc()?;
-                    if crc_merged != crc_original {
-                        entry.set_crc(crc_merged);
+                }
+            }
+            // the segment of the latest commit (and all after it) might still
+            // be appended to:
+            let sealed = latest_commit_offset.map_or(0, segment_of);
+            let compacted: Vec<usize> = (0..min(sealed, ranges.len().saturating_sub(1)))
+                .filter(|i| {
+                    garbage[*i] > 0
+                        && garbage[*i] as f64 >= policy.min_garbage_ratio * versions[*i] as f64
+                })
+                .collect();
+            // a removal can only be purged if no other version of its key
+            // survives the compaction, otherwise the older version would
+            // become the latest version of the key again:
+            for versions in offsets.values() {
+                if let Some(latest) = versions.last().filter(|latest| latest.is_removed) {
+                    let is_purged = |v: &BlobVersion| {
+                        compacted.contains(&segment_of(v.offset))
+                            && (v.offset == latest.offset || !retained.contains(&v.offset))
+                    };
+                    if versions.iter().all(is_purged) {
+                        retained.remove(&latest.offset);
                     }
-                    entry.write_to(&mut storage).await?;
-                    crc = Hasher::new();
                 }
-                offset += entry_length as u64;
             }
 
-            storage.flush().await?;
-            storage.stop_merge().await?;
-            storage.flush().await?;
+            let cipher = self.cipher.as_ref();
+            // compacting from the last segment to the first ensures that the
+            // ranges of all segments that are yet to be compacted are unchanged:
+            for i in compacted.into_iter().rev() {
+                let range = ranges[i].clone();
+                let mut batch = Batch::new(range.start);
+                let copied = copy_retained(
+                    &mut storage,
+                    range.start,
+                    range.end,
+                    &retained,
+                    &mut batch,
+                    u64::MAX,
+                    cipher,
+                );
+                copied.await?;
+                let bytes = batch.take();
+                let relocated = relocations(&retained, &range, &bytes).await?;
+                storage.replace_segment(i, &bytes).await?;
+                let shift = |offset: u64| offset - (range.end - range.start) + bytes.len() as u64;
+                for versions in offsets.values_mut() {
+                    versions.retain_mut(|v| {
+                        if v.offset >= range.end {
+                            v.offset = shift(v.offset);
+                        } else if range.contains(&v.offset) {
+                            match relocated.get(&v.offset) {
+                                Some(offset) => v.offset = *offset,
+                                None => return false,
+                            }
+                        }
+                        true
+                    });
+                }
+                offsets.retain(|_, versions| !versions.is_empty());
+                *latest_commit_offset = latest_commit_offset.map(|offset| match offset {
+                    offset if offset >= range.end => shift(offset),
+                    offset => offset,
+                });
+            }
+            log_merge(&self.name, started, len_before, storage.len());
+        }
+        self.write_hint().await
+    }
+}
+
+fn log_merge(name: &str, started: u64, len_before: u64, len_after: u64) {
+    info!(
+        "Merged store {} from {} to {} bytes in {}ms",
+        name,
+        len_before,
+        len_after,
+        timestamp_now().saturating_sub(started)
+    );
+}
+
+/// A stream of all the commits of a store after the subscription was created,
+/// see [`KvStore::subscribe()`].
+///
+/// Commits can either be received using [`Subscription::recv()`] or by using
+/// the subscription as a [`Stream`]. All commits are buffered until they are
+/// received, so a subscription that is no longer needed should be dropped.
+/// Commits are only sent after they have been successfully written to storage,
+/// aborted or conflicting transactions are never sent.
+#[derive(Debug)]
+pub struct Subscription {
+    receiver: UnboundedReceiver<Commit>,
+}
+
+impl Subscription {
+    /// Waits for and returns the next commit, or `None` if the store has been
+    /// dropped.
+    pub async fn recv(&mut self) -> Option<Commit> {
+        self.receiver.recv().await
+    }
+}
+
+impl Stream for Subscription {
+    type Item = Commit;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Commit>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+#[derive(Debug)]
+struct Subscriber {
+    slots: Option<Vec<u8>>,
+    sender: UnboundedSender<Commit>,
+}
+
+/// A successfully committed transaction, as received by a [`Subscription`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commit {
+    /// Timestamp of the commit in milliseconds since the Unix epoch.
+    ///
+    /// Timestamps are strictly increasing, a commit that happens within the
+    /// same millisecond as the previous commit (or while the clock is behind
+    /// the previous commit) is timestamped 1ms after the previous commit.
+    pub timestamp: u64,
+    /// The keys that were written by the commit, ordered by their slot and
+    /// their serialized representation.
+    pub changes: Vec<Change>,
+}
+
+/// A key that was inserted or removed as part of a [`Commit`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Change {
+    /// The slot of the key.
+    pub slot: u8,
+    /// The key, serialized using the [`Codec`] of the store.
+    pub key: Vec<u8>,
+    /// True if the key was removed ("moved to trash"), false if inserted.
+    pub is_removed: bool,
+}
+
+impl Change {
+    fn new(blob_key: &[u8], is_removed: bool) -> Self {
+        let (slot, key) = blob_key
+            .split_last()
+            .expect("blob keys always contain a slot");
+        Self {
+            slot: *slot,
+            key: key.to_vec(),
+            is_removed,
+        }
+    }
+
+    /// Deserializes the key of the change, which must have been serialized
+    /// using the default [`MessagePack`] codec.
+    pub fn deserialize_key<K: DeserializeOwned>(&self) -> Result<K> {
+        self.deserialize_key_with(&MessagePack)
+    }
+
+    /// Deserializes the key of the change using the specified codec, which
+    /// must be the codec of the store that sent the change.
+    pub fn deserialize_key_with<K: DeserializeOwned>(&self, codec: &impl Codec) -> Result<K> {
+        codec
+            .decode(&self.key)
+            .map_err(|reason| Error::InvalidKeyError { reason })
+    }
+}
+
+/// The future returned by a closure passed to [`KvStore::transact()`].
+pub type Transaction<'t, T> = Pin<Box<dyn Future<Output = Result<T>> + 't>>;
+
+/// A policy that decides how often and after which delay a transaction is
+/// retried after a conflict, see [`KvStore::transact_with()`].
+///
+/// The delay starts at `initial_backoff` and doubles after every retry, up to
+/// `max_backoff`. The default policy retries up to 10 times, waiting between 1
+/// and 100 milliseconds.
+#[derive(Debug, Copy, Clone)]
+pub struct RetryPolicy {
+    /// The maximum number of retries after the first attempt.
+    pub max_retries: u32,
+    /// The delay in milliseconds before the first retry.
+    pub initial_backoff: u64,
+    /// The maximum delay in milliseconds between two retries.
+    pub max_backoff: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 10,
+            initial_backoff: 1,
+            max_backoff: 100,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Waits before the next retry and returns `true`, or returns `false`
+    /// without waiting if the specified number of retries already exhausts the
+    /// policy.
+    pub async fn backoff(&self, retries: u32) -> bool {
+        if retries >= self.max_retries {
+            return false;
+        }
+        let factor = 1u64.checked_shl(retries).unwrap_or(u64::MAX);
+        sleep(min(
+            self.initial_backoff.saturating_mul(factor),
+            self.max_backoff,
+        ))
+        .await;
+        true
+    }
+}
+
+/// A policy that decides which old versions are kept when merging a store.
+///
+/// The latest version of each key is always kept. Older versions are kept if
+/// _any_ of the conditions of the policy applies to them. The default policy
+/// keeps only the latest version of each key and empties the trash completely.
+#[derive(Debug, Copy, Clone)]
+pub struct MergePolicy {
+    /// Keeps all versions with a timestamp (in milliseconds since the Unix
+    /// epoch) after this cutoff.
+    pub keep_versions_newer_than: Option<u64>,
+    /// Keeps the last n versions of each key (including the latest version).
+    pub keep_last_n: usize,
+    /// Keeps removed values restorable from the trash for the specified number
+    /// of milliseconds after they were removed.
+    pub keep_trash_for: Option<u64>,
+}
+
+impl Default for MergePolicy {
+    fn default() -> Self {
+        Self {
+            keep_versions_newer_than: None,
+            keep_last_n: 1,
+            keep_trash_for: None,
+        }
+    }
+}
+
+impl MergePolicy {
+    fn retained(&self, versions: &[BlobVersion], now: u64) -> Vec<u64> {
+        let latest = match versions.last() {
+            Some(latest) => latest,
+            None => return Vec::new(),
+        };
+        let is_in_trash = latest.is_removed
+            && matches!(self.keep_trash_for, Some(t) if latest.timestamp.saturating_add(t) > now);
+        let last_unremoved = versions.iter().rposition(|v| !v.is_removed);
+        versions
+            .iter()
+            .enumerate()
+            .filter(|(i, v)| {
+                let versions_after = versions.len() - 1 - i;
+                versions_after < max(self.keep_last_n, 1)
+                    || matches!(self.keep_versions_newer_than, Some(t) if v.timestamp > t)
+                    || (is_in_trash && last_unremoved == Some(*i))
+            })
+            .map(|(_, v)| v.offset)
+            .collect()
+    }
+}
+
+/// A policy that decides which segments of a store are compacted and which
+/// versions are kept in the compacted segments, see
+/// [`KvStore::compact_with()`].
+///
+/// The default policy compacts all segments in which at least half of the
+/// versions would be discarded by the default [`MergePolicy`].
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Copy, Clone)]
+pub struct CompactionPolicy {
+    /// Decides which versions are kept in the compacted segments.
+    pub merge: MergePolicy,
+    /// Compacts only the segments in which at least this share (between 0.0
+    /// and 1.0) of all versions would be discarded.
+    pub min_garbage_ratio: f64,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for CompactionPolicy {
+    fn default() -> Self {
+        Self {
+            merge: MergePolicy::default(),
+            min_garbage_ratio: 0.5,
+        }
+    }
+}
+
+/// Serializes all retained kv entries and all transaction commits (with
+/// updated CRCs) between the offset and the end into the batch, stopping early
+/// at the end of the first transaction after `max_bytes` have been read, so
+/// that the batch never ends in the middle of a transaction. Unencrypted kv
+/// entries are encrypted if a cipher is specified. Returns the offset after the
+/// last copied entry.
+async fn copy_retained<R: ReadAt>(
+    storage: &mut R,
+    mut offset: u64,
+    end: u64,
+    retained: &HashSet<u64>,
+    batch: &mut Batch,
+    max_bytes: u64,
+    cipher: Option<&Cipher>,
+) -> Result<u64> {
+    let start = offset;
+    let mut is_in_transaction = false;
+    while offset < end && (offset - start < max_bytes || is_in_transaction) {
+        let entry = Entry::read_from(storage, offset).await?;
+        let entry_length = entry.len() as u64;
+        is_in_transaction = !entry.is_transaction_commit();
+
+        // all kv writes have Some(key), all transactions have None
+        if entry.is_chunk() {
+            // chunks are copied together with the manifest of their value
+        } else if entry.is_chunked() {
+            if retained.contains(&offset) {
+                let (k, old_chunks) = entry.into_manifest(offset, cipher)?;
+                let mut chunks = Vec::with_capacity(old_chunks.len());
+                for chunk in old_chunks {
+                    let entry = Entry::read_from(storage, chunk).await?;
+                    chunks.push(batch.push(&entry.encrypt_with(cipher)?));
+                }
+                let entry = Entry::kv_manifest(k, batch.next_offset(), &chunks)?;
+                batch.push(&entry.encrypt_with(cipher)?);
+            }
+        } else if entry.key.is_some() {
+            if retained.contains(&offset) {
+                batch.push(&entry.encrypt_with(cipher)?);
+            }
+        } else if entry.is_transaction_commit() {
+            batch.push_commit(entry);
+        }
+        offset += entry_length;
+    }
+    Ok(offset)
+}
+
+/// Returns the new offsets of the retained kv entries in the range, which have
+/// been copied (in the same order) into the bytes that replace the range.
+#[cfg(not(target_arch = "wasm32"))]
+async fn relocations(
+    retained: &HashSet<u64>,
+    range: &Range<u64>,
+    bytes: &[u8],
+) -> Result<HashMap<u64, u64>> {
+    let mut old_offsets: Vec<u64> = retained
+        .iter()
+        .copied()
+        .filter(|offset| range.contains(offset))
+        .collect();
+    old_offsets.sort_unstable();
+    let mut view = View::new(bytes.to_vec());
+    let mut new_offsets = Vec::with_capacity(old_offsets.len());
+    let mut offset = 0;
+    while offset < view.len() {
+        let entry = Entry::read_from(&mut view, offset).await?;
+        if entry.key.is_some() && !entry.is_chunk() {
+            new_offsets.push(range.start + offset);
+        }
+        offset += entry.len() as u64;
+    }
+    if old_offsets.len() != new_offsets.len() {
+        return Err(Error::InvalidEntryError {
+            reason: format!(
+                "Expected {} kv entries after compacting, but found {}",
+                old_offsets.len(),
+                new_offsets.len()
+            ),
+        });
+    }
+    Ok(old_offsets.into_iter().zip(new_offsets).collect())
+}
+
+/// A source of entries, either a (locked) storage or a view of a storage that
+/// can be read without locking, see [`Storage::view()`].
+#[async_trait(?Send)]
+trait ReadAt {
+    async fn read_at(&mut self, offset: u64, bytes: u32) -> Result<Vec<u8>>;
+}
+
+#[async_trait(?Send)]
+impl<S: Storage> ReadAt for MutexGuard<'_, S> {
+    async fn read_at(&mut self, offset: u64, bytes: u32) -> Result<Vec<u8>> {
+        Ok(self.read(offset, bytes).await?)
+    }
+}
+
+#[async_trait(?Send)]
+impl<S: Storage> ReadAt for S {
+    async fn read_at(&mut self, offset: u64, bytes: u32) -> Result<Vec<u8>> {
+        Ok(self.read(offset, bytes).await?)
+    }
+}
+
+#[async_trait(?Send)]
+impl ReadAt for View {
+    async fn read_at(&mut self, offset: u64, bytes: u32) -> Result<Vec<u8>> {
+        Ok(self.read(offset, bytes))
+    }
+}
+
+/// Serializes entries into a single buffer, so that a whole transaction can be
+/// appended to the storage with a single write.
+struct Batch {
+    offset: u64,
+    buf: Vec<u8>,
+    crc: Hasher,
+}
+
+impl Batch {
+    fn new(offset: u64) -> Self {
+        Self {
+            offset,
+            buf: Vec::new(),
+            crc: Hasher::new(),
+        }
+    }
+
+    /// Appends the kv entry to the batch and returns the offset that the entry
+    /// will have in the storage once the batch is written.
+    fn push(&mut self, entry: &Entry) -> u64 {
+        let offset = self.next_offset();
+        entry.update_crc(&mut self.crc);
+        entry.serialize_into(&mut self.buf);
+        offset
+    }
+
+    /// Appends the transaction commit (with the CRC of all kv entries pushed so
+    /// far) to the batch and returns its offset.
+    fn push_commit(&mut self, mut entry: Entry) -> u64 {
+        entry.update_crc(&mut self.crc);
+        entry.set_crc(mem::replace(&mut self.crc, Hasher::new()).finalize());
+        let offset = self.next_offset();
+        entry.serialize_into(&mut self.buf);
+        offset
+    }
+
+    /// Returns the offset that the next entry pushed to the batch will have.
+    fn next_offset(&self) -> u64 {
+        self.offset + self.buf.len() as u64
+    }
+
+    /// Removes and returns all entries serialized so far, but keeps the CRC of
+    /// the current transaction so that the batch can be continued.
+    fn take(&mut self) -> Vec<u8> {
+        let buf = mem::take(&mut self.buf);
+        self.offset += buf.len() as u64;
+        buf
+    }
+
+    async fn write_to<S: Storage>(self, storage: &mut S) -> Result<()> {
+        let offset = storage.write(&self.buf).await?;
+        if offset != self.offset {
+            return Err(Error::InvalidEntryError {
+                reason: format!(
+                    "Batch was written at offset {}, but expected offset {}",
+                    offset, self.offset
+                ),
+            });
         }
-        init_store(self).await?;
         Ok(())
     }
 }
 
+/// Reads the value of the kv entry at the specified offset, reassembling the
+/// value from its chunks if the entry is the manifest of a chunked value.
+async fn read_value<R: ReadAt>(
+    storage: &mut R,
+    offset: u64,
+    cipher: Option<&Cipher>,
+) -> Result<Option<Value>> {
+    let entry = Entry::read_from(storage, offset).await?;
+    if !entry.is_chunked() {
+        return entry.into_value(cipher);
+    }
+    let (_, chunks) = entry.into_manifest(offset, cipher)?;
+    let mut value = Vec::new();
+    for chunk in chunks {
+        let entry = Entry::read_from(storage, chunk).await?;
+        if !entry.is_chunk() {
+            return Err(Error::InvalidEntryError {
+                reason: format!("Expected a chunk at offset {}", chunk),
+            });
+        }
+        value.extend(entry.into_value(cipher)?.unwrap_or_default());
+    }
+    Ok(Some(value))
+}
+
+/// Returns the name of the storage that holds the hint of the store with the
+/// specified name.
+pub fn hint_name(name: &str) -> String {
+    format!("{}.hint", name)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Hint {
+    offset: u64,
+    commit_offset: u64,
+    commit_crc: u32,
+    latest_timestamp: u64,
+    keys: BTreeMap<Vec<u8>, Vec<BlobVersion>>,
+}
+
+/// The isolation level of a snapshot, which decides which concurrent
+/// transactions are considered to be in conflict with the snapshot.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Isolation {
+    /// A snapshot conflicts with all transactions that were committed after the
+    /// snapshot was created and changed a value that the snapshot has read or
+    /// created or removed a key in a range that the snapshot has scanned (see
+    /// [`Snapshot::range()`], [`Snapshot::scan_prefix()`] and
+    /// [`Snapshot::keys()`]).
+    Serializable,
+    /// A snapshot only conflicts with transactions that were committed after
+    /// the snapshot was created and wrote to a key that the snapshot also
+    /// writes to (regardless of the values that were read).
+    Snapshot,
+}
+
 #[derive(Debug, Copy, Clone)]
 enum SnapshotBoundary {
     Timestamp(u64),
@@ -294,14 +1479,20 @@ enum SnapshotBoundary {
 /// persisted at the end of a successful transaction, until then all writes
 /// simply mutate an in-memory `HashMap`.
 ///
-/// Transactions provide some basic ACID guarantees and must be
-/// [serializable](https://en.wikipedia.org/wiki/Serializability), meaning that
-/// a transaction can only be committed if it does not conflict with a
-/// previously committed transaction. If a transaction `t1` reads any key-value
-/// pair (even a version with an older timestamp) that is modified and committed
-/// in a later transaction `t2` before `t1` is comitted, `t1` will fail with an
-/// [`Error::TransactionConflict`] and must be explicitly rerun by user of the
-/// store. In other words, the following transaction behaviour will lead to a
+/// Snapshots of the store at a past point in time can be created using
+/// [`KvStore::at()`] and are read-only.
+///
+/// Transactions provide some basic ACID guarantees and are
+/// [serializable](https://en.wikipedia.org/wiki/Serializability) by default,
+/// meaning that a transaction can only be committed if it does not conflict
+/// with a previously committed transaction. If a transaction `t1` reads any
+/// key-value pair (even a version with an older timestamp) whose value is
+/// changed by a later transaction `t2` that is committed before `t1`, `t1` will
+/// fail with an [`Error::TransactionConflict`] and must be explicitly rerun by
+/// user of the store (or automatically, by using [`KvStore::transact()`]).
+/// Keys whose versions were only listed (without reading their values) and
+/// keys that were overwritten with an identical value do not cause conflicts.
+/// In other words, the following transaction behaviour will lead to a
 /// conflict:
 ///
 /// ```text
@@ -313,18 +1504,58 @@ enum SnapshotBoundary {
 /// | commit: err |
 /// +-------------+
 /// ```
-pub struct Snapshot<'a, S: Storage> {
-    store: &'a KvStore<S>,
+///
+/// Snapshots created using [`KvStore::current_with()`] and
+/// [`Isolation::Snapshot`] only detect write-write conflicts instead, so that
+/// `t1` fails only if both `t1` and `t2` write to the same key.
+pub struct Snapshot<'a, S: Storage, C: Codec = MessagePack> {
+    store: &'a KvStore<S, C>,
     snapshot_timestamp: u64,
     latest_timestamp: u64,
     latest_offset: u64,
+    generation: u64,
+    boundary: SnapshotBoundary,
+    is_read_only: bool,
+    isolation: Isolation,
     transaction_entries: HashMap<Vec<u8>, Option<Vec<u8>>>,
+    id: u64,
+    savepoints: Vec<(u64, UndoLog)>,
+    next_savepoint: u64,
     cached_entries: Mutex<HashMap<Vec<u8>, ValuesByVersion>>,
+    read_keys: Mutex<HashSet<Vec<u8>>>,
+    read_ranges: Mutex<Vec<ScannedRange>>,
+    view: Mutex<Option<View>>,
 }
 
 type ValuesByVersion = HashMap<Version, Option<Vec<u8>>>;
 
-impl<'a, S: Storage> Snapshot<'a, S> {
+/// The keys of a slot that were scanned by a snapshot, so that keys that are
+/// created or removed in the range by other transactions can be detected.
+struct ScannedRange {
+    slot: u8,
+    range: (Bound<Vec<u8>>, Bound<Vec<u8>>),
+    prefix: Vec<u8>,
+}
+
+impl ScannedRange {
+    fn keys<'k, T>(
+        &'k self,
+        offsets: &'k BTreeMap<Vec<u8>, T>,
+    ) -> impl Iterator<Item = (&'k Vec<u8>, &'k T)> {
+        offsets
+            .range(self.range.clone())
+            .take_while(move |(k, _)| k.starts_with(&self.prefix))
+            .filter(move |(k, _)| self.contains(k))
+    }
+
+    fn contains(&self, k: &Vec<u8>) -> bool {
+        k.last() == Some(&self.slot)
+            && k[..k.len() - 1].starts_with(&self.prefix)
+            && self.range.contains(k)
+    }
+}
+
+impl<'a, S: Storage, C: Codec> Snapshot<'a, S, C> {
     /// Returns the (file-)name of the store associated with this snapshot.
     pub fn name(&self) -> &str {
         self.store.name()
@@ -340,7 +1571,10 @@ impl<'a, S: Storage> Snapshot<'a, S> {
         V: DeserializeOwned,
     {
         let versions = self.versions(slot, k).await?;
-        blob_to_serde_value(self.get_bytes(slot, k, versions.last().copied()).await?)
+        blob_to_serde_value(
+            &self.store.codec,
+            self.get_bytes(slot, k, versions.last().copied()).await?,
+        )
     }
 
     /// Returns the latest _non-removed_ value associated with a slot and key from
@@ -359,7 +1593,10 @@ impl<'a, S: Storage> Snapshot<'a, S> {
     {
         let versions = self.versions(slot, k).await?;
         let unremoved = versions.iter().filter(|v| !v.is_removed);
-        blob_to_serde_value(self.get_bytes(slot, k, unremoved.last().copied()).await?)
+        blob_to_serde_value(
+            &self.store.codec,
+            self.get_bytes(slot, k, unremoved.last().copied()).await?,
+        )
     }
 
     /// Returns the specified version of the value with the given slot and key.
@@ -368,17 +1605,36 @@ impl<'a, S: Storage> Snapshot<'a, S> {
         K: Serialize,
         V: DeserializeOwned,
     {
-        blob_to_serde_value(self.get_bytes(slot, k, Some(version)).await?)
+        blob_to_serde_value(
+            &self.store.codec,
+            self.get_bytes(slot, k, Some(version)).await?,
+        )
+    }
+
+    /// Returns the latest value associated with a slot and key as raw bytes,
+    /// without deserializing it, see [`Snapshot::insert_raw()`].
+    ///
+    /// Like [`Snapshot::get()`], returns `None` if the value has been removed.
+    pub async fn get_raw<K>(&self, slot: u8, k: &K) -> Result<Option<Vec<u8>>>
+    where
+        K: Serialize,
+    {
+        let versions = self.versions(slot, k).await?;
+        self.get_bytes(slot, k, versions.last().copied()).await
     }
 
     async fn get_bytes<K>(&self, slot: u8, k: &K, v: Option<Version>) -> Result<Option<Vec<u8>>>
     where
         K: Serialize,
     {
-        let k = &serde_to_blob_key(slot, k)?;
+        self.get_blob(&serde_to_blob_key(&self.store.codec, slot, k)?, v)
+            .await
+    }
+
+    async fn get_blob(&self, k: &[u8], v: Option<Version>) -> Result<Option<Vec<u8>>> {
         let mut cached_entries = self.cached_entries.lock().await;
         if !cached_entries.contains_key(k) {
-            cached_entries.insert(k.clone(), HashMap::new());
+            cached_entries.insert(k.to_vec(), HashMap::new());
         }
         if let Some(version) = v {
             if let Some(entry) = self.transaction_entries.get(k) {
@@ -386,19 +1642,50 @@ impl<'a, S: Storage> Snapshot<'a, S> {
                     return Ok(entry.clone());
                 }
             }
+            self.read_keys.lock().await.insert(k.to_vec());
             let versions = cached_entries.get_mut(k).unwrap();
             if let Some(entry) = versions.get(&version) {
+                self.store.counters.lock().await.cache_hits += 1;
                 return Ok(entry.clone());
             }
 
             if let Some(offset) = version.offset {
-                let entry = Entry::read_from(&mut self.store.storage.lock().await, offset).await?;
-                versions.insert(version, entry.val.clone());
-                Ok(entry.val)
+                self.check_generation().await?;
+                let cached = self.store.cache.lock().await.get(self.generation, offset);
+                if let Some(val) = cached {
+                    self.store.counters.lock().await.cache_hits += 1;
+                    versions.insert(version, val.clone());
+                    return Ok(val);
+                }
+                let cipher = self.store.cipher.as_ref();
+                let mut view = self.view.lock().await;
+                let val = match view.as_mut() {
+                    Some(view) if offset < view.len() => read_value(view, offset, cipher).await?,
+                    _ => {
+                        let mut storage = self.store.storage.lock().await;
+                        // the store might have been merged while unlocked:
+                        self.check_generation().await?;
+                        *view = storage.view();
+                        // views do not necessarily cover all committed entries:
+                        match view.as_mut() {
+                            Some(view) if offset < view.len() => {
+                                read_value(view, offset, cipher).await?
+                            }
+                            _ => read_value(&mut storage, offset, cipher).await?,
+                        }
+                    }
+                };
+                self.store.counters.lock().await.cache_misses += 1;
+                let mut cache = self.store.cache.lock().await;
+                cache.insert(self.generation, offset, val.clone());
+                versions.insert(version, val.clone());
+                Ok(val)
             } else {
                 Ok(None)
             }
         } else {
+            // reading a key that does not exist conflicts with its creation:
+            self.read_keys.lock().await.insert(k.to_vec());
             Ok(None)
         }
     }
@@ -412,13 +1699,18 @@ impl<'a, S: Storage> Snapshot<'a, S> {
     where
         K: Serialize,
     {
-        let k = &serde_to_blob_key(slot, k)?;
-        let up_until = self.latest_time_or_offset();
-        let mut versions: Vec<Version> =
-            versions_up_until(self.store.offsets.lock().await.get(k), up_until)
-                .into_iter()
-                .map(|v| v.into())
-                .collect();
+        self.blob_versions(&serde_to_blob_key(&self.store.codec, slot, k)?)
+            .await
+    }
+
+    async fn blob_versions(&self, k: &[u8]) -> Result<Vec<Version>> {
+        let offsets = self.store.offsets.lock().await;
+        self.check_generation().await?;
+        let mut versions: Vec<Version> = versions_up_until(offsets.get(k), self.boundary)
+            .into_iter()
+            .map(|v| v.into())
+            .collect();
+        drop(offsets);
         if let Some(entry) = self.transaction_entries.get(k) {
             versions.push(Version {
                 offset: None,
@@ -442,28 +1734,136 @@ impl<'a, S: Storage> Snapshot<'a, S> {
         })
     }
 
-    /// Returns all non-removed keys of the specified index in the store.
+    /// Returns all non-removed keys of the specified index in the store,
+    /// ordered by their serialized representation.
     ///
     /// Since all keys are stored in memory, this operation is quite fast as it
     /// does not need to access the persistent storage.
     pub async fn keys<K: DeserializeOwned>(&self, slot: u8) -> Result<Vec<K>> {
+        let offsets = self.store.offsets.lock().await;
+        self.check_generation().await?;
+        self.read_ranges.lock().await.push(ScannedRange {
+            slot,
+            range: (Bound::Unbounded, Bound::Unbounded),
+            prefix: Vec::new(),
+        });
         let mut keys: Vec<K> = Vec::new();
-        for (key, versions) in self.store.offsets.lock().await.iter() {
-            let versions = versions_up_until(Some(versions), self.latest_time_or_offset());
+        for (key, versions) in offsets.iter() {
+            let versions = versions_up_until(Some(versions), self.boundary);
             if let Some(s) = key.last() {
-                if *s == slot && !versions.last().unwrap().is_removed {
-                    let key = rmp_serde::decode::from_read(&key[..key.len() - 1]).map_err(|e| {
-                        Error::InvalidKeyError {
-                            reason: format!("{}", e),
-                        }
-                    });
-                    keys.push(key?);
+                if *s == slot && matches!(versions.last(), Some(v) if !v.is_removed) {
+                    keys.push(blob_to_serde_key(&self.store.codec, key)?);
                 }
             }
         }
         Ok(keys)
     }
 
+    /// Returns all non-removed key-value pairs of the specified slot whose keys
+    /// fall inside the range, ordered by their serialized keys.
+    ///
+    /// Keys are compared using their serialized representation, which for the
+    /// default [MessagePack](https://msgpack.org/) codec matches the natural
+    /// ordering for unsigned ints and for strings of equal length, but not
+    /// necessarily for other types (see [`codec`]). Like [`Snapshot::get()`], the scan
+    /// includes the (uncommitted) writes of the current transaction, but
+    /// ignores writes of other transactions and skips values that were "moved
+    /// to trash".
+    pub async fn range<K, V, R>(&self, slot: u8, range: R) -> Result<Vec<(K, V)>>
+    where
+        K: Serialize + DeserializeOwned,
+        V: DeserializeOwned,
+        R: RangeBounds<K>,
+    {
+        // Since the encodings of codecs are self-delimiting, no serialized key is
+        // a proper prefix of another. Appending the max byte to a serialized
+        // key thus yields a bound that sorts after the key in every slot, but
+        // before all keys that are greater than the key.
+        let start = match range.start_bound() {
+            Bound::Included(k) => Bound::Included(serde_to_blob(&self.store.codec, k)?),
+            Bound::Excluded(k) => {
+                Bound::Excluded(serde_to_blob_key(&self.store.codec, u8::MAX, k)?)
+            }
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(k) => {
+                Bound::Included(serde_to_blob_key(&self.store.codec, u8::MAX, k)?)
+            }
+            Bound::Excluded(k) => Bound::Excluded(serde_to_blob(&self.store.codec, k)?),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        if is_empty_range(&start, &end) {
+            return Ok(Vec::new());
+        }
+        let keys = self.keys_in_range(slot, (start, end), &[]).await?;
+        self.entries_of(keys).await
+    }
+
+    /// Returns all non-removed key-value pairs of the specified slot whose
+    /// serialized keys start with the specified prefix, ordered by their
+    /// serialized keys.
+    ///
+    /// The prefix is matched against the serialized representation of the
+    /// keys. Using the default [MessagePack](https://msgpack.org/) codec,
+    /// tuples are serialized as arrays (a header containing the length of the
+    /// array, followed by the elements), this can be used to scan composite
+    /// keys: all 2-tuples whose first element is the int `1` share the prefix
+    /// `[0x92, 0x01]`, for example.
+    pub async fn scan_prefix<K, V>(&self, slot: u8, prefix: &[u8]) -> Result<Vec<(K, V)>>
+    where
+        K: DeserializeOwned,
+        V: DeserializeOwned,
+    {
+        let range = (Bound::Included(prefix.to_vec()), Bound::Unbounded);
+        let keys = self.keys_in_range(slot, range, prefix).await?;
+        self.entries_of(keys).await
+    }
+
+    async fn keys_in_range(
+        &self,
+        slot: u8,
+        range: (Bound<Vec<u8>>, Bound<Vec<u8>>),
+        prefix: &[u8],
+    ) -> Result<Vec<Vec<u8>>> {
+        let offsets = self.store.offsets.lock().await;
+        self.check_generation().await?;
+        let scanned = ScannedRange {
+            slot,
+            range,
+            prefix: prefix.to_vec(),
+        };
+        let mut keys: Vec<Vec<u8>> = scanned.keys(&offsets).map(|(k, _)| k.clone()).collect();
+        keys.extend(
+            self.transaction_entries
+                .keys()
+                .filter(|k| scanned.contains(k))
+                .cloned(),
+        );
+        keys.sort();
+        keys.dedup();
+        self.read_ranges.lock().await.push(scanned);
+        Ok(keys)
+    }
+
+    async fn entries_of<K, V>(&self, keys: Vec<Vec<u8>>) -> Result<Vec<(K, V)>>
+    where
+        K: DeserializeOwned,
+        V: DeserializeOwned,
+    {
+        let mut entries = Vec::new();
+        for k in keys {
+            let versions = self.blob_versions(&k).await?;
+            if let Some(v) = blob_to_serde_value(
+                &self.store.codec,
+                self.get_blob(&k, versions.last().copied()).await?,
+            )? {
+                entries.push((blob_to_serde_key(&self.store.codec, &k)?, v));
+            }
+        }
+        Ok(entries)
+    }
+
     /// Inserts a key-value pair in the store, superseding older versions.
     ///
     /// All inserts are buffered in memory and only persisted at the end of a
@@ -472,22 +1872,41 @@ impl<'a, S: Storage> Snapshot<'a, S> {
     /// storage, as from the point of view of the transaction both inserts
     /// happen at the same time and thus only the last one for each key must be
     /// stored as a new version in the store.
+    ///
+    /// Values of any size can be inserted, large values are transparently
+    /// split into several chunks when they are written to storage.
     pub fn insert<K, V>(&mut self, slot: u8, k: K, v: V) -> Result<()>
     where
         K: Serialize,
         V: Serialize,
     {
-        let v = serde_to_blob_value(&v)?;
+        let v = serde_to_blob_value(&self.store.codec, &v)?;
         self.insert_bytes(slot, k, v)?;
         Ok(())
     }
 
+    /// Inserts a key with a value of raw bytes, which are stored as-is instead
+    /// of being serialized by the codec of the store.
+    ///
+    /// This avoids serializing values twice if they are already available as
+    /// serialized bytes. The value can be read using [`Snapshot::get_raw()`],
+    /// or using [`Snapshot::get()`] if the bytes are a valid serialization
+    /// produced by the codec of the store. Only the value is raw, the key is
+    /// still serialized using the codec.
+    pub fn insert_raw<K>(&mut self, slot: u8, k: K, v: Vec<u8>) -> Result<()>
+    where
+        K: Serialize,
+    {
+        self.insert_bytes(slot, k, v)
+    }
+
     fn insert_bytes<K>(&mut self, slot: u8, k: K, v: Vec<u8>) -> Result<()>
     where
         K: Serialize,
     {
-        let k = serde_to_blob_key(slot, &k)?;
-        self.transaction_entries.insert(k, Some(v));
+        self.check_writable()?;
+        let k = serde_to_blob_key(&self.store.codec, slot, &k)?;
+        self.write_entry(k, Some(v));
         Ok(())
     }
 
@@ -506,11 +1925,21 @@ impl<'a, S: Storage> Snapshot<'a, S> {
     where
         K: Serialize,
     {
-        let k = serde_to_blob_key(slot, &k)?;
-        self.transaction_entries.insert(k, None);
+        self.check_writable()?;
+        let k = serde_to_blob_key(&self.store.codec, slot, &k)?;
+        self.write_entry(k, None);
         Ok(())
     }
 
+    fn write_entry(&mut self, k: Vec<u8>, v: Option<Vec<u8>>) {
+        if let Some((_, undo)) = self.savepoints.last_mut() {
+            if !undo.contains_key(&k) {
+                undo.insert(k.clone(), self.transaction_entries.get(&k).cloned());
+            }
+        }
+        self.transaction_entries.insert(k, v);
+    }
+
     /// Aborts the current transaction, discarding all of its write operations.
     pub async fn abort(mut self) -> Result<()> {
         // Clear transaction explicitly to suppress warning on drop:
@@ -521,77 +1950,216 @@ impl<'a, S: Storage> Snapshot<'a, S> {
     /// Commits the current transaction, persisting all of its write operations
     /// as new versions in the store.
     pub async fn commit(mut self) -> Result<()> {
+        self.check_writable()?;
         let entries = mem::take(&mut self.transaction_entries);
         if entries.is_empty() {
             return Ok(());
         }
         let mut storage = self.store.storage.lock().await;
+        self.check_generation().await?;
         let mut offsets = self.store.offsets.lock().await;
-        {
-            for k in self.cached_entries.lock().await.keys() {
-                if let Some(versions) = offsets.get(k) {
-                    let version = versions
-                        .last()
-                        .unwrap_or_else(|| panic!("could not find last version of key {:?}", k));
-
-                    // the value that was read in this transaction has since
-                    // been modified by another transaction and committed, the
-                    // current transaction is thus in conflict and cannot be
-                    // committed
-                    if version.offset >= self.latest_offset {
-                        return Err(Error::TransactionConflict);
+        self.check_conflicts(&mut storage, &offsets, &entries)
+            .await?;
+
+        // all entries of the transaction are serialized into a single batch,
+        // so that the whole transaction is appended with a single write:
+        let mut batch = Batch::new(storage.len());
+        let uncommitted_offsets = self.serialize_entries(entries, &mut batch)?;
+        let t_commit = timestamp_after(*self.store.latest_timestamp.lock().await);
+        let commit_offset = batch.push_commit(Entry::transaction_commit(t_commit)?);
+        batch.write_to(&mut *storage).await?;
+        self.finish_commit(
+            &mut storage,
+            &mut offsets,
+            uncommitted_offsets,
+            t_commit,
+            commit_offset,
+        )
+        .await
+    }
+
+    async fn check_conflicts(
+        &self,
+        storage: &mut MutexGuard<'_, S>,
+        offsets: &BTreeMap<Vec<u8>, Vec<BlobVersion>>,
+        entries: &HashMap<Vec<u8>, Option<Vec<u8>>>,
+    ) -> Result<()> {
+        match self.isolation {
+            Isolation::Serializable => {
+                for k in self.read_keys.lock().await.iter() {
+                    if let Some(versions) = offsets.get(k) {
+                        // the value that was read in this transaction has since
+                        // been modified by another transaction and committed,
+                        // the current transaction is thus in conflict and
+                        // cannot be committed
+                        if self.is_changed(storage, versions).await? {
+                            return Err(self.conflict().await);
+                        }
+                    }
+                }
+                // keys that were created or removed in a scanned range are
+                // phantoms that would have changed the result of the scan:
+                for scanned in self.read_ranges.lock().await.iter() {
+                    for (_, versions) in scanned.keys(offsets) {
+                        if self.is_created_or_removed(versions) {
+                            return Err(self.conflict().await);
+                        }
+                    }
+                }
+            }
+            Isolation::Snapshot => {
+                for k in entries.keys() {
+                    if let Some(version) = offsets.get(k).and_then(|versions| versions.last()) {
+                        if version.offset >= self.latest_offset {
+                            return Err(self.conflict().await);
+                        }
                     }
                 }
             }
         }
+        Ok(())
+    }
 
-        let mut crc = Hasher::new();
+    /// Serializes all kv entries of the transaction (but not its commit) into
+    /// the batch and returns their keys and offsets.
+    fn serialize_entries(
+        &self,
+        entries: HashMap<Vec<u8>, Option<Vec<u8>>>,
+        batch: &mut Batch,
+    ) -> Result<Vec<(Vec<u8>, u64, bool)>> {
+        let cipher = self.store.cipher.as_ref();
         let mut uncommitted_offsets = Vec::with_capacity(entries.len());
         for (k, buf) in entries.into_iter() {
-            if let Some(buf) = buf {
-                let entry = Entry::kv_insert(k, buf)?;
-                let offset = entry.write_to(&mut storage).await?;
-                entry.update_crc(&mut crc);
-                uncommitted_offsets.push((entry.key.unwrap(), offset, false));
+            if buf.as_ref().is_some_and(|buf| buf.len() > MAX_CHUNK_SIZE) {
+                let buf = buf.unwrap();
+                let mut chunks = Vec::new();
+                for chunk in buf.chunks(MAX_CHUNK_SIZE) {
+                    let entry = Entry::kv_chunk(chunk.to_vec())?.encrypt_with(cipher)?;
+                    chunks.push(batch.push(&entry));
+                }
+                let entry = Entry::kv_manifest(k.clone(), batch.next_offset(), &chunks)?;
+                let entry = entry.encrypt_with(cipher)?;
+                uncommitted_offsets.push((k, batch.push(&entry), false));
+            } else if let Some(buf) = buf {
+                let entry = Entry::kv_insert(k.clone(), buf)?.encrypt_with(cipher)?;
+                uncommitted_offsets.push((k, batch.push(&entry), false));
             } else {
-                let entry = Entry::kv_remove(k)?;
-                let offset = entry.write_to(&mut storage).await?;
-                entry.update_crc(&mut crc);
-                uncommitted_offsets.push((entry.key.unwrap(), offset, true));
+                let entry = Entry::kv_remove(k.clone())?.encrypt_with(cipher)?;
+                uncommitted_offsets.push((k, batch.push(&entry), true));
             }
         }
+        Ok(uncommitted_offsets)
+    }
 
-        let t_commit = timestamp_now_monotonic(self.latest_timestamp);
-        let mut entry = Entry::transaction_commit(t_commit)?;
-        entry.update_crc(&mut crc);
-        entry.set_crc(crc.finalize());
-        entry.write_to(&mut storage).await?;
-
+    /// Adds the versions of a transaction that has been written to storage to
+    /// the key directory and notifies all subscribers of the commit.
+    async fn finish_commit(
+        &self,
+        storage: &mut MutexGuard<'_, S>,
+        offsets: &mut BTreeMap<Vec<u8>, Vec<BlobVersion>>,
+        uncommitted_offsets: Vec<(Vec<u8>, u64, bool)>,
+        t_commit: u64,
+        commit_offset: u64,
+    ) -> Result<()> {
+        let mut changes = Vec::with_capacity(uncommitted_offsets.len());
         for (k, offset, is_removed) in uncommitted_offsets {
-            offsets
-                .entry(k.to_vec())
-                .or_insert_with(Vec::new)
-                .push(BlobVersion {
-                    offset,
-                    is_removed,
-                    timestamp: t_commit,
-                });
+            changes.push(Change::new(&k, is_removed));
+            offsets.entry(k).or_default().push(BlobVersion {
+                offset,
+                is_removed,
+                timestamp: t_commit,
+            });
         }
         *self.store.latest_timestamp.lock().await = t_commit;
+        *self.store.latest_commit_offset.lock().await = Some(commit_offset);
         storage.flush().await?;
+        self.store.counters.lock().await.commits += 1;
+        debug!(
+            "Committed {} entries to store {} at {}",
+            changes.len(),
+            self.store.name,
+            t_commit
+        );
+        changes.sort();
+        self.store.notify(t_commit, &changes).await;
         Ok(())
     }
 
-    fn latest_time_or_offset(&self) -> SnapshotBoundary {
-        if self.snapshot_timestamp == self.latest_timestamp {
-            SnapshotBoundary::Offset(self.latest_offset)
+    /// Checks whether versions committed after the snapshot was created have
+    /// changed the (removed or unremoved) value visible to the snapshot.
+    fn is_created_or_removed(&self, versions: &[BlobVersion]) -> bool {
+        let is_removed = |v: Option<&BlobVersion>| v.is_none_or(|v| v.is_removed);
+        let visible = versions
+            .iter()
+            .take_while(|v| v.offset < self.latest_offset)
+            .last();
+        is_removed(visible) != is_removed(versions.last())
+    }
+
+    async fn is_changed(
+        &self,
+        storage: &mut MutexGuard<'_, S>,
+        versions: &[BlobVersion],
+    ) -> Result<bool> {
+        let visible = versions
+            .iter()
+            .take_while(|v| v.offset < self.latest_offset);
+        let visible: Vec<&BlobVersion> = visible.collect();
+        if visible.len() == versions.len() {
+            return Ok(false);
+        }
+        let is_removed = |versions: &[&BlobVersion]| versions.last().is_none_or(|v| v.is_removed);
+        let last_unremoved = |versions: &[&BlobVersion]| {
+            versions
+                .iter()
+                .rev()
+                .find(|v| !v.is_removed)
+                .map(|v| v.offset)
+        };
+        let all: Vec<&BlobVersion> = versions.iter().collect();
+        if is_removed(&visible) != is_removed(&all) {
+            return Ok(true);
+        }
+        match (last_unremoved(&visible), last_unremoved(&all)) {
+            (Some(before), Some(after)) => {
+                let cipher = self.store.cipher.as_ref();
+                let before = read_value(storage, before, cipher).await?;
+                let after = read_value(storage, after, cipher).await?;
+                Ok(before != after)
+            }
+            (before, after) => Ok(before != after),
+        }
+    }
+
+    async fn check_generation(&self) -> Result<()> {
+        if self.store.is_inconsistent.load(Ordering::SeqCst) {
+            return Err(Error::InconsistentStore);
+        }
+        // the store has been merged since the snapshot was created, so all of
+        // the offsets known to the snapshot are invalid:
+        if *self.store.generation.lock().await != self.generation {
+            Err(self.conflict().await)
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn conflict(&self) -> Error {
+        self.store.counters.lock().await.conflicts += 1;
+        Error::TransactionConflict
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        self.store.check_writable()?;
+        if self.is_read_only {
+            Err(Error::ReadOnlySnapshot)
         } else {
-            SnapshotBoundary::Timestamp(self.latest_timestamp)
+            Ok(())
         }
     }
 }
 
-impl<S: Storage> Drop for Snapshot<'_, S> {
+impl<S: Storage, C: Codec> Drop for Snapshot<'_, S, C> {
     fn drop(&mut self) {
         if !self.transaction_entries.is_empty() {
             warn!("Snapshot with changes was dropped without being committed!");
@@ -599,88 +2167,219 @@ impl<S: Storage> Drop for Snapshot<'_, S> {
     }
 }
 
-async fn init_store<S: Storage>(store: &mut KvStore<S>) -> Result<()> {
+async fn init_store<S: Storage, C: Codec>(store: &KvStore<S, C>) -> Result<()> {
+    let mut storage = store.storage.lock().await;
+    if !store.is_read_only {
+        recover_intent(&store.name, &mut *storage).await?;
+    }
+    load_store(store, &mut storage).await
+}
+
+async fn load_store<S: Storage, C: Codec>(
+    store: &KvStore<S, C>,
+    storage: &mut MutexGuard<'_, S>,
+) -> Result<()> {
     let mut uncommitted = Vec::new();
     let mut crc = Hasher::new();
     let mut latest_timestamp = 0;
+    let mut latest_commit_offset = None;
     let mut offset = 0;
-    let max_offset = store.len().await;
+    let mut offsets = store.offsets.lock().await;
+    offsets.clear();
+    store.cache.lock().await.clear();
+    match read_hint(store, storage).await {
+        Ok(Some(hint)) => {
+            *offsets = hint.keys;
+            latest_timestamp = hint.latest_timestamp;
+            latest_commit_offset = Some(hint.commit_offset);
+            offset = hint.offset;
+        }
+        Ok(None) => {}
+        Err(e) => warn!("Ignoring hint of store {} due to {:?}", store.name, e),
+    }
+    let mut end_of_last_commit = offset;
+    let mut corrupt_offset = None;
+    let max_offset = storage.len();
     while offset < max_offset {
-        let entry = Entry::read_from(&mut store.storage.lock().await, offset).await?;
+        let entry = match Entry::read_from(storage, offset).await {
+            Ok(entry) if offset + entry.len() as u64 <= max_offset => entry,
+            // the last entry was only partially written and never committed:
+            Ok(_) | Err(Error::StorageError(storage::Error::OffsetError { .. })) => break,
+            Err(Error::StorageError(e)) => return Err(Error::StorageError(e)),
+            Err(_) => {
+                corrupt_offset = Some(offset);
+                break;
+            }
+        };
         let entry_length = entry.len() as u64;
-        let offsets = &mut (*store.offsets.lock().await);
 
-        if !entry.is_transaction() {
+        if entry.is_chunk() {
+            // chunks are only reachable through the manifest of their value:
             entry.update_crc(&mut crc);
-            uncommitted.push((entry.key.unwrap(), offset, entry.val.is_none()));
+        } else if !entry.is_transaction() {
+            entry.update_crc(&mut crc);
+            let is_removed = entry.val.is_none();
+            // decryption errors are only returned for transactions that are not
+            // corrupt, corrupt transactions are truncated instead:
+            uncommitted.push((entry.into_key(store.cipher.as_ref()), offset, is_removed));
         } else if entry.is_transaction_commit() {
             entry.update_crc(&mut crc);
             let crc_kv_writes = crc.finalize();
             let crc_commit = entry.crc()?;
             if crc_kv_writes != crc_commit {
-                warn!("Truncating corrupt store at offset {}", offset);
-                store
-                    .storage
-                    .lock()
-                    .await
-                    .truncate(offset)
-                    .await
-                    .expect("Error while truncating storage to remove corrupt data");
+                corrupt_offset = Some(offset);
                 break;
             }
 
             let timestamp_commit = u64_from_bytes(entry.val.as_ref().unwrap())?;
-            for (k, offset, is_removed) in uncommitted.iter() {
+            for (k, offset, is_removed) in uncommitted.drain(..) {
                 offsets
-                    .entry(k.to_vec())
+                    .entry(k?)
                     .or_insert_with(Vec::new)
                     .push(BlobVersion {
-                        offset: *offset,
-                        is_removed: *is_removed,
+                        offset,
+                        is_removed,
                         timestamp: timestamp_commit,
                     });
             }
-            uncommitted.clear();
             crc = Hasher::new();
             latest_timestamp = max(latest_timestamp, timestamp_commit);
+            latest_commit_offset = Some(offset);
+            end_of_last_commit = offset + entry_length;
         }
 
         offset += entry_length as u64;
     }
-    store.latest_timestamp = Mutex::new(latest_timestamp);
+    if let (Some(offset), true) = (corrupt_offset, store.is_read_only) {
+        return Err(Error::CorruptDataError(offset));
+    }
+    // Truncating the orphaned writes of a transaction that was never committed
+    // is necessary even if nothing is corrupt, because they would otherwise be
+    // included in the CRC of the next transaction when the store is reopened.
+    if end_of_last_commit < max_offset && !store.is_read_only {
+        match corrupt_offset {
+            Some(offset) => warn!(
+                "Truncating corrupt store at offset {} (corrupt data at offset {})",
+                end_of_last_commit, offset
+            ),
+            None => warn!(
+                "Truncating uncommitted writes at offset {}",
+                end_of_last_commit
+            ),
+        }
+        storage
+            .truncate(end_of_last_commit)
+            .await
+            .expect("Error while truncating storage to remove corrupt data");
+    }
+    *store.latest_timestamp.lock().await = latest_timestamp;
+    *store.latest_commit_offset.lock().await = latest_commit_offset;
     Ok(())
 }
 
-fn serde_to_blob_key(slot: u8, k: &impl Serialize) -> Result<Vec<u8>> {
-    rmp_serde::encode::to_vec(k)
-        .map(|mut v| {
-            v.push(slot);
-            v
-        })
-        .map_err(|e| Error::InvalidKeyError {
-            reason: format!("Unable to serialize: {}", e),
-        })
+async fn read_hint<S: Storage, C: Codec>(
+    store: &KvStore<S, C>,
+    storage: &mut MutexGuard<'_, S>,
+) -> Result<Option<Hint>> {
+    let mut hint_storage = if store.is_read_only {
+        // a read-only store must never create (or purge) a hint storage:
+        match storage
+            .open_existing_sibling(&hint_name(&store.name))
+            .await?
+        {
+            Some(hint_storage) => hint_storage,
+            None => return Ok(None),
+        }
+    } else {
+        storage.open_sibling(&hint_name(&store.name)).await?
+    };
+    let len = hint_storage.len();
+    if len <= BYTES_CRC as u64 {
+        // opening the hint storage creates it, so remove it again if it is empty:
+        drop(hint_storage);
+        if !store.is_read_only {
+            storage.purge_sibling(&hint_name(&store.name)).await?;
+        }
+        return Ok(None);
+    }
+    let bytes = hint_storage.read(0, len as u32).await?;
+    let (crc, payload) = bytes.split_at(BYTES_CRC);
+    if u32_from_bytes(crc)? != crc32fast::hash(payload) {
+        return Err(Error::CorruptDataError(0));
+    }
+    let payload = match &store.cipher {
+        Some(cipher) => cipher.decrypt(payload, &[])?,
+        None => payload.to_vec(),
+    };
+    let hint: Hint =
+        rmp_serde::decode::from_read(payload.as_slice()).map_err(|e| Error::InvalidEntryError {
+            reason: format!("Unable to deserialize hint: {}", e),
+        })?;
+
+    // The hint is only valid if the last commit that it covers is still found
+    // at the same offset of the store (which is not the case if the store has
+    // since been merged, truncated or replaced).
+    if hint.commit_offset >= hint.offset || hint.offset > storage.len() {
+        return Ok(None);
+    }
+    let commit = Entry::read_from(storage, hint.commit_offset).await?;
+    if !commit.is_transaction_commit()
+        || commit.crc()? != hint.commit_crc
+        || hint.commit_offset + commit.len() as u64 != hint.offset
+    {
+        return Ok(None);
+    }
+    Ok(Some(hint))
 }
 
-fn serde_to_blob_value(v: &impl Serialize) -> Result<Vec<u8>> {
-    rmp_serde::encode::to_vec(v).map_err(|e| Error::InvalidValueError {
-        reason: format!("Unable to serialize: {}", e),
+fn serde_to_blob_key(codec: &impl Codec, slot: u8, k: &impl Serialize) -> Result<Vec<u8>> {
+    serde_to_blob(codec, k).map(|mut v| {
+        v.push(slot);
+        v
     })
 }
 
-fn blob_to_serde_value<V>(v: Option<Vec<u8>>) -> Result<Option<V>>
+fn serde_to_blob(codec: &impl Codec, k: &impl Serialize) -> Result<Vec<u8>> {
+    codec
+        .encode(k)
+        .map_err(|reason| Error::InvalidKeyError { reason })
+}
+
+fn blob_to_serde_key<K: DeserializeOwned>(codec: &impl Codec, k: &[u8]) -> Result<K> {
+    codec
+        .decode(&k[..k.len() - 1])
+        .map_err(|reason| Error::InvalidKeyError { reason })
+}
+
+fn is_empty_range(start: &Bound<Vec<u8>>, end: &Bound<Vec<u8>>) -> bool {
+    match (start, end) {
+        (Bound::Included(s), Bound::Included(e)) => s > e,
+        (Bound::Included(s), Bound::Excluded(e))
+        | (Bound::Excluded(s), Bound::Included(e))
+        | (Bound::Excluded(s), Bound::Excluded(e)) => s >= e,
+        _ => false,
+    }
+}
+
+fn serde_to_blob_value(codec: &impl Codec, v: &impl Serialize) -> Result<Vec<u8>> {
+    codec
+        .encode(v)
+        .map_err(|reason| Error::InvalidValueError { reason })
+}
+
+fn blob_to_serde_value<V>(codec: &impl Codec, v: Option<Vec<u8>>) -> Result<Option<V>>
 where
     V: DeserializeOwned,
 {
     v.map(|v| {
-        rmp_serde::decode::from_read(&v[..]).map_err(|e| Error::InvalidValueError {
-            reason: format!("{}", e),
-        })
+        codec
+            .decode(&v)
+            .map_err(|reason| Error::InvalidValueError { reason })
     })
     .transpose()
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 struct BlobVersion {
     offset: u64,
     is_removed: bool,
@@ -718,7 +2417,19 @@ struct Entry {
 // 0b____000_00_000
 //       ||| || \\\__ bytes required to store the value size (0-6 bytes)
 //       ||| \\______ bytes required to store the key size (0-3 bytes)
-//       \\\_________ flags reserved for later use
+//       ||\_________ value is compressed (only if compiled with "compression")
+//       |\__________ key and value are encrypted (only if compiled with "encryption")
+//       \___________ value is a manifest of the offsets of the chunks of a large value
+//
+// Encrypted keys are authenticated together with the flags of the header, and
+// encrypted values together with the flags and the encrypted key, so that
+// values cannot be swapped between keys without failing to decrypt.
+//
+// Kv entries with an empty key (which is never a valid blob key, since blob
+// keys always end with their slot) hold the chunks of large values. They are
+// not part of the key directory and only reachable through their manifest,
+// which stores the offsets of the chunks relative to the offset of the manifest
+// (so that whole transactions can be moved without rewriting them).
 impl Entry {
     fn transaction_commit(timestamp: u64) -> Result<Self> {
         let mut buf = vec![0; BYTES_TIMESTAMP_FULL];
@@ -727,9 +2438,33 @@ impl Entry {
     }
 
     fn kv_insert(k: Vec<u8>, v: Value) -> Result<Self> {
+        #[cfg(feature = "compression")]
+        {
+            let compressed = lz4_flex::compress_prepend_size(&v);
+            if compressed.len() < v.len() {
+                let mut entry = Self::new(Some(k), Some(compressed))?;
+                entry.header |= FLAG_COMPRESSED;
+                return Ok(entry);
+            }
+        }
         Self::new(Some(k), Some(v))
     }
 
+    fn kv_chunk(v: Value) -> Result<Self> {
+        Self::kv_insert(Vec::new(), v)
+    }
+
+    fn kv_manifest(k: Vec<u8>, offset: u64, chunks: &[u64]) -> Result<Self> {
+        let distances: Vec<u64> = chunks.iter().map(|chunk| offset - chunk).collect();
+        let manifest =
+            rmp_serde::encode::to_vec(&distances).map_err(|e| Error::InvalidEntryError {
+                reason: format!("Unable to serialize manifest: {}", e),
+            })?;
+        let mut entry = Self::new(Some(k), Some(manifest))?;
+        entry.header |= FLAG_CHUNKED;
+        Ok(entry)
+    }
+
     fn kv_remove(k: Vec<u8>) -> Result<Self> {
         Self::new(Some(k), None)
     }
@@ -760,9 +2495,11 @@ impl Entry {
         })
     }
 
-    async fn read_from<S: Storage>(storage: &mut MutexGuard<'_, S>, offset: u64) -> Result<Self> {
+    async fn read_from<R: ReadAt>(storage: &mut R, offset: u64) -> Result<Self> {
         let max_length_of_header_and_sizes = 1 + 3 + 6;
-        let mut header_and_sizes = storage.read(offset, max_length_of_header_and_sizes).await?;
+        let mut header_and_sizes = storage
+            .read_at(offset, max_length_of_header_and_sizes)
+            .await?;
         if header_and_sizes.is_empty() {
             return Err(Error::InvalidEntryError {
                 reason: "Offset exceeds storage bounds".to_string(),
@@ -802,9 +2539,9 @@ impl Entry {
         let val_size = u32_from_bytes(&sizes[bytes_key_size as usize..])?;
         let offset_content = offset_sizes + bytes_sizes as u64;
 
-        if key_size > (1 << 16) {
+        if key_size >= (1 << 24) {
             return Err(Error::InvalidEntryError {
-                reason: "Key size is > max size of 2^16 bytes".to_string(),
+                reason: "Key size is > max size of 2^24 bytes".to_string(),
             });
         }
         if val_size > (1 << 24) {
@@ -817,7 +2554,7 @@ impl Entry {
         let (key, val, crc) = if is_transaction {
             if val_size > 0 {
                 let bytes_content = key_size + val_size + BYTES_CRC as u32;
-                let content = storage.read(offset_content, bytes_content).await?;
+                let content = storage.read_at(offset_content, bytes_content).await?;
                 if content.len() < val_size as usize {
                     return Err(Error::InvalidEntryError {
                         reason: "Invalid length of entry content buffer".to_string(),
@@ -833,7 +2570,7 @@ impl Entry {
             }
         } else {
             let bytes_content = key_size + val_size;
-            let content = storage.read(offset_content, bytes_content).await?;
+            let content = storage.read_at(offset_content, bytes_content).await?;
             if content.len() < key_size as usize {
                 return Err(Error::InvalidEntryError {
                     reason: "Invalid length of entry content buffer".to_string(),
@@ -855,19 +2592,18 @@ impl Entry {
         })
     }
 
-    async fn write_to<S: Storage>(&self, storage: &mut MutexGuard<'_, S>) -> Result<u64> {
-        let offset = storage.write(&[self.header]).await?;
-        storage.write(&self.sizes).await?;
+    fn serialize_into(&self, buf: &mut Vec<u8>) {
+        buf.push(self.header);
+        buf.extend(&self.sizes);
         if let Some(k) = &self.key {
-            storage.write(k).await?;
+            buf.extend(k);
         }
         if let Some(v) = &self.val {
-            storage.write(v).await?;
+            buf.extend(v);
         }
         if let Some(crc) = &self.crc {
-            storage.write(crc).await?;
+            buf.extend(crc);
         }
-        Ok(offset)
     }
 
     fn set_crc(&mut self, crc: u32) {
@@ -899,6 +2635,84 @@ impl Entry {
             + self.crc.as_ref().map_or(0, |crc| crc.len())
     }
 
+    fn encrypt_with(self, cipher: Option<&Cipher>) -> Result<Self> {
+        let cipher = match cipher {
+            Some(cipher) if self.key.is_some() && self.header & FLAG_ENCRYPTED == 0 => cipher,
+            _ => return Ok(self),
+        };
+        let flags = (self.header & (FLAG_COMPRESSED | FLAG_CHUNKED)) | FLAG_ENCRYPTED;
+        let k = match self.key.as_ref().unwrap() {
+            k if k.is_empty() => Vec::new(),
+            k => cipher.encrypt(k, &[flags])?,
+        };
+        let aad = [&[flags], k.as_slice()].concat();
+        let v = self.val.map(|v| cipher.encrypt(&v, &aad)).transpose()?;
+        let mut entry = Self::new(Some(k), v)?;
+        entry.header |= flags;
+        Ok(entry)
+    }
+
+    fn flags(&self) -> u8 {
+        self.header & (FLAG_COMPRESSED | FLAG_ENCRYPTED | FLAG_CHUNKED)
+    }
+
+    fn into_key(self, cipher: Option<&Cipher>) -> Result<Vec<u8>> {
+        let flags = self.flags();
+        let k = self
+            .key
+            .expect("Trying to read the key of a transaction entry");
+        if flags & FLAG_ENCRYPTED != 0 {
+            cipher::decrypt(cipher, &k, &[flags])
+        } else {
+            Ok(k)
+        }
+    }
+
+    fn into_value(self, cipher: Option<&Cipher>) -> Result<Option<Value>> {
+        let flags = self.flags();
+        let v = match self.val {
+            Some(v) if flags & FLAG_ENCRYPTED != 0 => {
+                let k = self.key.as_deref().unwrap_or_default();
+                cipher::decrypt(cipher, &v, &[&[flags], k].concat())?
+            }
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        if self.header & FLAG_COMPRESSED != 0 {
+            Ok(Some(decompress(&v)?))
+        } else {
+            Ok(Some(v))
+        }
+    }
+
+    fn into_manifest(self, offset: u64, cipher: Option<&Cipher>) -> Result<(Vec<u8>, Vec<u64>)> {
+        let key = Self {
+            header: self.header,
+            sizes: Vec::new(),
+            key: self.key.clone(),
+            val: None,
+            crc: None,
+        };
+        let manifest = self.into_value(cipher)?.unwrap_or_default();
+        let distances: Vec<u64> =
+            rmp_serde::from_read_ref(&manifest).map_err(|e| Error::InvalidEntryError {
+                reason: format!("Unable to deserialize manifest: {}", e),
+            })?;
+        let chunks = distances
+            .iter()
+            .map(|d| offset.saturating_sub(*d))
+            .collect();
+        Ok((key.into_key(cipher)?, chunks))
+    }
+
+    fn is_chunk(&self) -> bool {
+        matches!(&self.key, Some(k) if k.is_empty())
+    }
+
+    fn is_chunked(&self) -> bool {
+        self.header & FLAG_CHUNKED != 0
+    }
+
     fn is_transaction(&self) -> bool {
         self.key.is_none()
     }
@@ -908,6 +2722,20 @@ impl Entry {
     }
 }
 
+#[cfg(feature = "compression")]
+fn decompress(v: &[u8]) -> Result<Value> {
+    lz4_flex::decompress_size_prepended(v).map_err(|e| Error::InvalidEntryError {
+        reason: format!("Unable to decompress value: {}", e),
+    })
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompress(_v: &[u8]) -> Result<Value> {
+    Err(Error::InvalidEntryError {
+        reason: "Value is compressed, but compression is not enabled".to_string(),
+    })
+}
+
 fn u64_from_bytes(bytes: &[u8]) -> Result<u64> {
     if bytes.len() > 8 {
         Err(Error::InvalidIntLength {
@@ -0,0 +1,299 @@
+//! Atomic commits of transactions across several stores, see
+//! [`Snapshot::commit_all()`].
+use crate::{
+    codec::Codec,
+    storage::Storage,
+    timestamp::{timestamp_after, timestamp_now},
+    u32_from_bytes, Batch, Entry, Error, KvStore, Result, Snapshot, BYTES_CRC,
+};
+use crc32fast::Hasher;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{mem, sync::atomic::Ordering};
+use tokio::sync::MutexGuard;
+
+impl<'a, S: Storage, C: Codec> Snapshot<'a, S, C> {
+    /// Commits the transactions of all the snapshots atomically, so that either
+    /// all or none of them are persisted, even if the snapshots belong to
+    /// different stores.
+    ///
+    /// The snapshots are committed using a two-phase commit: First, all stores
+    /// are locked and checked for conflicts, then each transaction (including
+    /// its commit) is serialized and stored as an intent next to its store.
+    /// Only after all intents have been persisted, the decision to commit is
+    /// stored next to the first store and each transaction is appended to its
+    /// store with a single write, just like a regular commit. If any of the
+    /// snapshots is in conflict or the intents cannot be stored, the intents
+    /// are removed again and none of the stores is modified.
+    ///
+    /// If the process crashes in the middle of a multi-store commit, the
+    /// intent is found when the store is opened again and the interrupted
+    /// transaction is either written to the store or discarded depending on the
+    /// decision. This requires all stores to be opened as siblings of each
+    /// other (so that they can find the decision, see
+    /// [`Storage::open_sibling()`]) and does not work for memory storages,
+    /// whose siblings are not persisted.
+    ///
+    /// If a transaction cannot be written to its store after the decision to
+    /// commit has been stored, the transactions of the stores before it are
+    /// already committed, but the transactions of the store and all stores
+    /// after it are not (and their storages might contain a partially written
+    /// transaction). These stores are marked as inconsistent and all of their
+    /// reads, writes and commits fail with an [`Error::InconsistentStore`]
+    /// until they are reopened, which writes their transactions to the stores.
+    ///
+    /// Fails with an [`Error::DuplicateStore`] if two snapshots belong to the
+    /// same store.
+    pub async fn commit_all(snapshots: Vec<Self>) -> Result<()> {
+        let mut transactions = Vec::with_capacity(snapshots.len());
+        for mut snapshot in snapshots {
+            snapshot.check_writable()?;
+            let entries = mem::take(&mut snapshot.transaction_entries);
+            if !entries.is_empty() {
+                transactions.push((snapshot, entries));
+            }
+        }
+        // stores are always locked in the same order to avoid deadlocks:
+        transactions.sort_by_key(|(snapshot, _)| {
+            (
+                snapshot.store.name.clone(),
+                snapshot.store as *const _ as usize,
+            )
+        });
+        for pair in transactions.windows(2) {
+            if std::ptr::eq(pair[0].0.store, pair[1].0.store) {
+                return Err(Error::DuplicateStore {
+                    name: pair[0].0.store.name.clone(),
+                });
+            }
+        }
+        if transactions.len() == 1 {
+            let (mut snapshot, entries) = transactions.pop().unwrap();
+            snapshot.transaction_entries = entries;
+            return snapshot.commit().await;
+        }
+
+        let mut storages = Vec::with_capacity(transactions.len());
+        let mut offsets = Vec::with_capacity(transactions.len());
+        for (snapshot, _) in transactions.iter() {
+            let store: &'a KvStore<S, C> = snapshot.store;
+            storages.push(store.storage.lock().await);
+            snapshot.check_generation().await?;
+            offsets.push(store.offsets.lock().await);
+        }
+        for (i, (snapshot, entries)) in transactions.iter().enumerate() {
+            snapshot
+                .check_conflicts(&mut storages[i], &offsets[i], entries)
+                .await?;
+        }
+
+        let t_prepare = timestamp_now();
+        let transaction = format!("{}.{}", transactions[0].0.store.name, t_prepare);
+        let participants: Vec<String> = transactions
+            .iter()
+            .map(|(snapshot, _)| snapshot.store.name.clone())
+            .collect();
+        let mut prepared = Vec::with_capacity(transactions.len());
+        let mut intents = Vec::with_capacity(transactions.len());
+        for (i, (snapshot, entries)) in transactions.iter_mut().enumerate() {
+            let offset = storages[i].len();
+            let t_commit = timestamp_after(*snapshot.store.latest_timestamp.lock().await);
+            let mut batch = Batch::new(offset);
+            let uncommitted_offsets = snapshot.serialize_entries(mem::take(entries), &mut batch)?;
+            let commit_offset = batch.push_commit(Entry::transaction_commit(t_commit)?);
+            intents.push(Intent {
+                transaction: transaction.clone(),
+                participants: participants.clone(),
+                offset,
+                batch: batch.buf.clone(),
+            });
+            prepared.push((batch, uncommitted_offsets, t_commit, commit_offset));
+        }
+
+        // phase 1: all transactions are persisted as intents, but none of the
+        // stores is modified yet
+        let prepare = async {
+            for (i, intent) in intents.iter().enumerate() {
+                write_intent(&mut storages[i], intent).await?;
+            }
+            let mut decision = storages[0]
+                .open_sibling(&decision_name(&transaction))
+                .await?;
+            decision.write(transaction.as_bytes()).await?;
+            decision.flush().await?;
+            Ok::<(), Error>(())
+        };
+        if let Err(e) = prepare.await {
+            for (i, participant) in participants.iter().enumerate() {
+                storages[i].purge_sibling(&intent_name(participant)).await?;
+            }
+            storages[0]
+                .purge_sibling(&decision_name(&transaction))
+                .await?;
+            return Err(e);
+        }
+        drop(intents);
+
+        // phase 2: the decision to commit has been persisted and is final, so
+        // each transaction is written to its store (with a single write, so
+        // that a transaction is never split, not even across segments)
+        for (i, (batch, uncommitted_offsets, t_commit, commit_offset)) in
+            prepared.into_iter().enumerate()
+        {
+            let snapshot = &transactions[i].0;
+            let commit = async {
+                batch.write_to(&mut *storages[i]).await?;
+                storages[i].flush().await?;
+                storages[i]
+                    .purge_sibling(&intent_name(&participants[i]))
+                    .await?;
+                snapshot
+                    .finish_commit(
+                        &mut storages[i],
+                        &mut offsets[i],
+                        uncommitted_offsets,
+                        t_commit,
+                        commit_offset,
+                    )
+                    .await
+            };
+            if let Err(e) = commit.await {
+                // the remaining transactions can only be finished by recovering
+                // their intents when the stores are reopened:
+                for (snapshot, _) in transactions[i..].iter() {
+                    snapshot.store.is_inconsistent.store(true, Ordering::SeqCst);
+                }
+                return Err(e);
+            }
+        }
+        storages[0]
+            .purge_sibling(&decision_name(&transaction))
+            .await?;
+        Ok(())
+    }
+}
+
+/// A multi-store transaction that has been prepared for a store, but not yet
+/// written to it, see [`Snapshot::commit_all()`].
+#[derive(Debug, Serialize, Deserialize)]
+struct Intent {
+    transaction: String,
+    participants: Vec<String>,
+    offset: u64,
+    batch: Vec<u8>,
+}
+
+/// Finishes or discards a multi-store transaction that was interrupted after
+/// its intent was stored, see [`Snapshot::commit_all()`].
+///
+/// If the decision to commit the transaction was stored, the transaction of
+/// the intent is written to the store (unless it has already been written
+/// before the crash), otherwise the intent is simply removed.
+pub(crate) async fn recover_intent<S: Storage>(name: &str, storage: &mut S) -> Result<()> {
+    let intent = match read_intent(&*storage, name).await {
+        Ok(Some(intent)) => intent,
+        Ok(None) => return Ok(()),
+        Err(e) => {
+            // the intent is written before the decision, so there is nothing
+            // to recover if it is corrupt:
+            warn!("Ignoring intent of store {} due to {:?}", name, e);
+            return storage
+                .purge_sibling(&intent_name(name))
+                .await
+                .map_err(Error::from);
+        }
+    };
+    let decision_name = decision_name(&intent.transaction);
+    let mut decision = storage.open_sibling(&decision_name).await?;
+    let is_committed =
+        decision.read(0, decision.len() as u32).await? == intent.transaction.as_bytes();
+    drop(decision);
+    if is_committed {
+        let len = storage.len();
+        let end = intent.offset + intent.batch.len() as u64;
+        let is_written = len >= end
+            && storage
+                .read(intent.offset, intent.batch.len() as u32)
+                .await?
+                == intent.batch;
+        if !is_written {
+            warn!(
+                "Committing the interrupted transaction {} at offset {}",
+                intent.transaction, intent.offset
+            );
+            // a partially written transaction is replaced as a whole:
+            if len > intent.offset {
+                storage.truncate(intent.offset).await?;
+            }
+            let batch = Batch {
+                offset: intent.offset,
+                buf: intent.batch,
+                crc: Hasher::new(),
+            };
+            batch.write_to(storage).await?;
+            storage.flush().await?;
+        }
+    } else {
+        warn!(
+            "Discarding the interrupted transaction {} at offset {}",
+            intent.transaction, intent.offset
+        );
+    }
+    storage.purge_sibling(&intent_name(name)).await?;
+
+    // the decision is kept until all participants have recovered:
+    let mut is_pending = false;
+    for participant in intent.participants.iter() {
+        if let Ok(Some(other)) = read_intent(&*storage, participant).await {
+            is_pending |= other.transaction == intent.transaction;
+        }
+    }
+    if !is_pending || !is_committed {
+        storage.purge_sibling(&decision_name).await?;
+    }
+    Ok(())
+}
+
+/// Returns the intent stored next to the store with the specified name, or
+/// `None` if the store is not part of an interrupted multi-store transaction.
+async fn read_intent<S: Storage>(storage: &S, name: &str) -> Result<Option<Intent>> {
+    let mut intent_storage = storage.open_sibling(&intent_name(name)).await?;
+    let len = intent_storage.len();
+    if len <= BYTES_CRC as u64 {
+        // opening the intent storage creates it, so remove it again if empty:
+        drop(intent_storage);
+        storage.purge_sibling(&intent_name(name)).await?;
+        return Ok(None);
+    }
+    let bytes = intent_storage.read(0, len as u32).await?;
+    let (crc, payload) = bytes.split_at(BYTES_CRC);
+    if u32_from_bytes(crc)? != crc32fast::hash(payload) {
+        return Err(Error::CorruptDataError(0));
+    }
+    let intent = rmp_serde::decode::from_read(payload).map_err(|e| Error::InvalidEntryError {
+        reason: format!("Unable to deserialize intent: {}", e),
+    })?;
+    Ok(Some(intent))
+}
+
+async fn write_intent<S: Storage>(storage: &mut MutexGuard<'_, S>, intent: &Intent) -> Result<()> {
+    let payload = rmp_serde::encode::to_vec(intent).map_err(|e| Error::InvalidEntryError {
+        reason: format!("Unable to serialize intent: {}", e),
+    })?;
+    let name = intent_name(storage.name());
+    storage.purge_sibling(&name).await?;
+    let mut intent_storage = storage.open_sibling(&name).await?;
+    let mut bytes = crc32fast::hash(&payload).to_le_bytes().to_vec();
+    bytes.extend(payload);
+    intent_storage.write(&bytes).await?;
+    intent_storage.flush().await?;
+    Ok(())
+}
+
+fn intent_name(name: &str) -> String {
+    format!("{}.intent", name)
+}
+
+fn decision_name(transaction: &str) -> String {
+    format!("{}.decision", transaction)
+}
@@ -0,0 +1,198 @@
+//! Shipping of committed transactions from a primary store to its replicas,
+//! see [`KvStore::transactions_since()`] and [`KvStore::apply()`].
+use crate::{
+    codec::Codec,
+    integrity::{scan_transaction, ScannedTransaction},
+    storage::{Storage, View},
+    u32_from_bytes, u64_from_bytes, BlobVersion, Change, Entry, Error, KvStore, Result,
+    BYTES_COMMIT, BYTES_CRC,
+};
+use serde::{Deserialize, Serialize};
+use std::{cmp::max, convert::TryFrom};
+
+impl<S: Storage, C: Codec> KvStore<S, C> {
+    /// Returns the transactions that were committed after the specified offset,
+    /// as frames that can be applied to a replica of the store.
+    ///
+    /// The offset must be the end of a transaction, usually the length of the
+    /// replica (see [`KvStore::len()`]) or the end of the last frame that was
+    /// applied to it. Together with [`KvStore::subscribe()`], this can be used
+    /// to continuously ship new transactions from a primary store to one or
+    /// more replicas, see [`KvStore::apply()`].
+    ///
+    /// At most (roughly) `max_bytes` of transactions are returned at once, but
+    /// always at least one transaction if the store contains a transaction
+    /// after the offset. Further transactions can be requested by calling this
+    /// method again with the end of the last frame, the store is only locked
+    /// while a single batch of frames is read. Returns an empty list if no
+    /// transactions were committed after the offset.
+    pub async fn transactions_since(
+        &self,
+        mut offset: u64,
+        max_bytes: u64,
+    ) -> Result<Vec<TransactionFrame>> {
+        let mut storage = self.storage.lock().await;
+        let end = match *self.latest_commit_offset.lock().await {
+            Some(commit_offset) => commit_offset + BYTES_COMMIT,
+            None => 0,
+        };
+        if offset > end {
+            return Err(Error::InvalidFrameError {
+                reason: format!("Offset {} exceeds the end of the store at {}", offset, end),
+            });
+        }
+        let mut parent = None;
+        if offset > 0 {
+            let commit_offset = offset.saturating_sub(BYTES_COMMIT);
+            let commit = Entry::read_from(&mut storage, commit_offset).await?;
+            if offset < BYTES_COMMIT
+                || !commit.is_transaction_commit()
+                || commit.len() as u64 != BYTES_COMMIT
+            {
+                return Err(Error::InvalidFrameError {
+                    reason: format!("Offset {} is not the end of a transaction", offset),
+                });
+            }
+            parent = Some(commit.crc()?);
+        }
+        let start = offset;
+        let mut frames = Vec::new();
+        while offset < end && (frames.is_empty() || offset - start < max_bytes) {
+            let end_of_transaction = match scan_transaction(&mut storage, offset, end).await? {
+                ScannedTransaction::Valid { end, .. } => end,
+                _ => return Err(Error::CorruptDataError(offset)),
+            };
+            let len = u32::try_from(end_of_transaction - offset).map_err(|_| {
+                Error::InvalidFrameError {
+                    reason: format!("Transaction at offset {} exceeds 4 GiB", offset),
+                }
+            })?;
+            let bytes = storage.read(offset, len).await?;
+            let crc = u32_from_bytes(&bytes[bytes.len() - BYTES_CRC..])?;
+            frames.push(TransactionFrame {
+                offset,
+                parent,
+                bytes,
+            });
+            parent = Some(crc);
+            offset = end_of_transaction;
+        }
+        Ok(frames)
+    }
+
+    /// Validates the transaction frame of another store and appends it to this
+    /// store, as if the transaction had been committed in this store.
+    ///
+    /// Frames must be applied in order and only to a replica that contains
+    /// exactly the transactions of the primary store before the frame, so a
+    /// replica should never commit transactions of its own. If the primary
+    /// store is merged, its frames do not continue the log of the replica
+    /// anymore and fail with an [`Error::InvalidFrameError`], in which case the
+    /// replica needs to be replaced with a backup of the primary store, see
+    /// [`KvStore::backup_to()`].
+    ///
+    /// Applying a frame writes to the replica, so replicas must be opened as
+    /// writable stores (frames applied to a read-only store fail with an
+    /// [`Error::ReadOnlyStore`]), but can still be read using snapshots while
+    /// frames are being applied, see [`KvStore::current()`].
+    pub async fn apply(&self, frame: &TransactionFrame) -> Result<()> {
+        self.check_writable()?;
+        let mut storage = self.storage.lock().await;
+        if frame.offset != storage.len() {
+            return Err(Error::InvalidFrameError {
+                reason: format!(
+                    "Frame starts at offset {}, but the store ends at offset {}",
+                    frame.offset,
+                    storage.len()
+                ),
+            });
+        }
+        let parent = match *self.latest_commit_offset.lock().await {
+            Some(offset) => Some(Entry::read_from(&mut storage, offset).await?.crc()?),
+            None => None,
+        };
+        if parent != frame.parent {
+            return Err(Error::InvalidFrameError {
+                reason: "Frame does not continue the last transaction of the store".to_string(),
+            });
+        }
+
+        let mut view = View::new(frame.bytes.clone());
+        let len = view.len();
+        match scan_transaction(&mut view, 0, len).await? {
+            ScannedTransaction::Valid { end, .. } if end == len => {}
+            _ => return Err(Error::CorruptDataError(frame.offset)),
+        }
+        let cipher = self.cipher.as_ref();
+        let mut uncommitted_offsets = Vec::new();
+        let mut t_commit = 0;
+        let mut commit_offset = 0;
+        let mut offset = 0;
+        while offset < len {
+            let entry = Entry::read_from(&mut view, offset).await?;
+            let entry_length = entry.len() as u64;
+            if entry.is_transaction_commit() {
+                t_commit = u64_from_bytes(entry.val.as_ref().unwrap())?;
+                commit_offset = frame.offset + offset;
+            } else if !entry.is_chunk() && !entry.is_transaction() {
+                let is_removed = entry.val.is_none();
+                let k = entry.into_key(cipher)?;
+                uncommitted_offsets.push((k, frame.offset + offset, is_removed));
+            }
+            offset += entry_length;
+        }
+
+        let written = storage.write(&frame.bytes).await?;
+        if written != frame.offset {
+            return Err(Error::InvalidEntryError {
+                reason: format!(
+                    "Frame was written at offset {}, but expected offset {}",
+                    written, frame.offset
+                ),
+            });
+        }
+        storage.flush().await?;
+
+        let mut offsets = self.offsets.lock().await;
+        let mut changes = Vec::with_capacity(uncommitted_offsets.len());
+        for (k, offset, is_removed) in uncommitted_offsets {
+            changes.push(Change::new(&k, is_removed));
+            offsets.entry(k).or_insert_with(Vec::new).push(BlobVersion {
+                offset,
+                is_removed,
+                timestamp: t_commit,
+            });
+        }
+        let mut latest_timestamp = self.latest_timestamp.lock().await;
+        *latest_timestamp = max(*latest_timestamp, t_commit);
+        drop(latest_timestamp);
+        *self.latest_commit_offset.lock().await = Some(commit_offset);
+        drop(offsets);
+        changes.sort();
+        self.notify(t_commit, &changes).await;
+        Ok(())
+    }
+}
+
+/// A committed transaction, as it is stored in the log of a store, see
+/// [`KvStore::transactions_since()`] and [`KvStore::apply()`].
+///
+/// Frames contain the raw (and possibly encrypted) entries of a transaction
+/// and can be serialized to ship them to a replica of the store.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionFrame {
+    /// The offset of the transaction in the log of the store.
+    pub offset: u64,
+    /// The CRC of the commit of the previous transaction in the log, or `None`
+    /// if the transaction is the first transaction of the store.
+    pub parent: Option<u32>,
+    /// The entries of the transaction, including its commit.
+    pub bytes: Vec<u8>,
+}
+
+impl TransactionFrame {
+    /// Returns the offset in the log of the store after the transaction.
+    pub fn end(&self) -> u64 {
+        self.offset + self.bytes.len() as u64
+    }
+}
@@ -0,0 +1,101 @@
+//! Savepoints that undo parts of a transaction, see [`Snapshot::savepoint()`].
+use crate::{codec::Codec, storage::Storage, Error, Result, Snapshot};
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// The entries of a transaction as they were before they were first written
+/// after a savepoint, `None` if the key was not written before the savepoint.
+pub(crate) type UndoLog = HashMap<Vec<u8>, Option<Option<Vec<u8>>>>;
+
+/// A savepoint inside of a transaction, which can be used to undo all writes
+/// made after it, see [`Snapshot::savepoint()`].
+///
+/// A savepoint can only be used with the snapshot that created it and only as
+/// long as it is still part of the nested savepoints of the snapshot.
+#[derive(Debug)]
+pub struct Savepoint {
+    snapshot: u64,
+    depth: usize,
+    id: u64,
+}
+
+/// Returns an id that is unique among all snapshots of the process, so that
+/// savepoints can be checked against the snapshot that created them.
+pub(crate) fn next_snapshot_id() -> u64 {
+    static NEXT_SNAPSHOT_ID: AtomicU64 = AtomicU64::new(0);
+    NEXT_SNAPSHOT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+impl<S: Storage, C: Codec> Snapshot<'_, S, C> {
+    /// Creates a savepoint that marks the current state of the transaction.
+    ///
+    /// All inserts and removes after the savepoint can be undone using
+    /// [`Snapshot::rollback_to()`] without aborting the whole transaction, or
+    /// kept using [`Snapshot::release()`]. Savepoints can be nested, rolling
+    /// back to or releasing a savepoint also rolls back or releases all
+    /// savepoints that were created after it. Savepoints only affect the
+    /// writes of the transaction, reads are not undone and will still be
+    /// checked for conflicts when the transaction is committed.
+    pub fn savepoint(&mut self) -> Savepoint {
+        let id = self.next_savepoint;
+        self.next_savepoint += 1;
+        self.savepoints.push((id, HashMap::new()));
+        Savepoint {
+            snapshot: self.id,
+            depth: self.savepoints.len() - 1,
+            id,
+        }
+    }
+
+    /// Undoes all inserts and removes made after the savepoint was created.
+    ///
+    /// Fails with an [`Error::UnknownSavepoint`] if the savepoint has already
+    /// been released or rolled back (or was created after a savepoint that
+    /// has been released or rolled back) and with an
+    /// [`Error::ForeignSavepoint`] if it was created by another snapshot.
+    pub fn rollback_to(&mut self, savepoint: Savepoint) -> Result<()> {
+        let i = self.savepoint_index(&savepoint)?;
+        for (_, undo) in self.savepoints.drain(i..).rev() {
+            for (k, entry) in undo {
+                match entry {
+                    Some(entry) => self.transaction_entries.insert(k, entry),
+                    None => self.transaction_entries.remove(&k),
+                };
+            }
+        }
+        Ok(())
+    }
+
+    /// Keeps all inserts and removes made after the savepoint was created as
+    /// part of the transaction (or of the enclosing savepoint, if any).
+    ///
+    /// Fails with an [`Error::UnknownSavepoint`] if the savepoint has already
+    /// been released or rolled back (or was created after a savepoint that
+    /// has been released or rolled back) and with an
+    /// [`Error::ForeignSavepoint`] if it was created by another snapshot.
+    pub fn release(&mut self, savepoint: Savepoint) -> Result<()> {
+        let i = self.savepoint_index(&savepoint)?;
+        let released: Vec<(u64, UndoLog)> = self.savepoints.drain(i..).collect();
+        if let Some((_, parent)) = self.savepoints.last_mut() {
+            // the enclosing savepoint keeps the oldest state of each entry:
+            for (_, undo) in released {
+                for (k, entry) in undo {
+                    parent.entry(k).or_insert(entry);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn savepoint_index(&self, savepoint: &Savepoint) -> Result<usize> {
+        if savepoint.snapshot != self.id {
+            return Err(Error::ForeignSavepoint);
+        }
+        match self.savepoints.get(savepoint.depth) {
+            Some((id, _)) if *id == savepoint.id => Ok(savepoint.depth),
+            _ => Err(Error::UnknownSavepoint),
+        }
+    }
+}
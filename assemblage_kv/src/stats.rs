@@ -0,0 +1,115 @@
+//! Statistics about the size and usage of a store, see [`KvStore::stats()`].
+use crate::{codec::Codec, storage::Storage, Entry, KvStore, Result};
+use std::collections::BTreeMap;
+
+impl<S: Storage, C: Codec> KvStore<S, C> {
+    /// Returns statistics about the size and usage of the store, see
+    /// [`Stats`].
+    ///
+    /// The live bytes are computed by reading the latest version of every key
+    /// from storage, so this method is as expensive as reading all values of
+    /// the store and should not be called on every commit.
+    pub async fn stats(&self) -> Result<Stats> {
+        let mut storage = self.storage.lock().await;
+        let offsets = self.offsets.lock().await;
+        let cipher = self.cipher.as_ref();
+        let mut stats = Stats {
+            total_bytes: storage.len(),
+            ..Stats::default()
+        };
+        for (k, versions) in offsets.iter() {
+            let latest = match versions.last() {
+                Some(latest) => latest,
+                None => continue,
+            };
+            let slot = stats.slots.entry(k[k.len() - 1]).or_default();
+            slot.versions += versions.len();
+            if latest.is_removed {
+                slot.trashed_keys += 1;
+            } else {
+                slot.keys += 1;
+            }
+            let entry = Entry::read_from(&mut storage, latest.offset).await?;
+            stats.live_bytes += entry.len() as u64;
+            if entry.is_chunked() {
+                let (_, chunks) = entry.into_manifest(latest.offset, cipher)?;
+                for chunk in chunks {
+                    stats.live_bytes += Entry::read_from(&mut storage, chunk).await?.len() as u64;
+                }
+            }
+        }
+        let counters = self.counters.lock().await;
+        stats.commits = counters.commits;
+        stats.conflicts = counters.conflicts;
+        stats.cache_hits = counters.cache_hits;
+        stats.cache_misses = counters.cache_misses;
+        Ok(stats)
+    }
+}
+
+/// Statistics about the size and usage of a store, see [`KvStore::stats()`].
+///
+/// The counters of commits, conflicts and cache hits only include the
+/// transactions of this instance of the store since it was opened.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    /// The total length of the storage in bytes.
+    pub total_bytes: u64,
+    /// The bytes of the latest version of each key (including removed keys),
+    /// which is roughly the length of the storage after a merge with the
+    /// default [`MergePolicy`](crate::MergePolicy).
+    pub live_bytes: u64,
+    /// The keys and versions of each slot that contains at least one key.
+    pub slots: BTreeMap<u8, SlotStats>,
+    /// The number of transactions that were committed.
+    pub commits: u64,
+    /// The number of reads and commits that failed with an
+    /// [`Error::TransactionConflict`](crate::Error::TransactionConflict).
+    pub conflicts: u64,
+    /// The number of values that were read from a cache instead of storage.
+    pub cache_hits: u64,
+    /// The number of values that had to be read from storage.
+    pub cache_misses: u64,
+}
+
+impl Stats {
+    /// Returns the fraction of the storage that would be discarded by a merge
+    /// with the default [`MergePolicy`](crate::MergePolicy), between 0.0 and 1.0.
+    pub fn garbage_ratio(&self) -> f64 {
+        if self.total_bytes == 0 {
+            0.0
+        } else {
+            self.total_bytes.saturating_sub(self.live_bytes) as f64 / self.total_bytes as f64
+        }
+    }
+
+    /// Returns the fraction of values that were read from a cache, or `None`
+    /// if no values have been read.
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        match self.cache_hits + self.cache_misses {
+            0 => None,
+            reads => Some(self.cache_hits as f64 / reads as f64),
+        }
+    }
+}
+
+/// The keys and versions of a single slot, see [`Stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SlotStats {
+    /// The number of keys whose latest version has not been removed.
+    pub keys: usize,
+    /// The number of keys whose latest version has been removed (and whose
+    /// values are thus in the trash).
+    pub trashed_keys: usize,
+    /// The number of versions of all keys, including removed versions.
+    pub versions: usize,
+}
+
+/// The counters of a store since it was opened, see [`Stats`].
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    pub(crate) commits: u64,
+    pub(crate) conflicts: u64,
+    pub(crate) cache_hits: u64,
+    pub(crate) cache_misses: u64,
+}
@@ -1,16 +1,20 @@
 //! A storage backend abstraction for kv stores, similar to an append-only file.
 pub mod file_storage;
 pub mod memory_storage;
+pub mod segmented_storage;
 pub mod web_storage;
 
 use async_trait::async_trait;
-use std::io;
+use std::{cmp::min, io, sync::Arc};
 
 #[cfg(target_arch = "wasm32")]
 pub use web_storage::WebStorage;
 
 #[cfg(not(target_arch = "wasm32"))]
-pub use file_storage::FileStorage;
+pub use file_storage::{Durability, FileOptions, FileStorage, OpenMode};
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use segmented_storage::{SegmentOptions, SegmentedStorage};
 
 pub use memory_storage::MemoryStorage;
 
@@ -65,6 +69,31 @@ pub trait Storage: Sized {
     /// Deletes the storage and all its contents.
     async fn purge<'a>(name: impl Into<String> + 'a) -> Result<()>;
 
+    /// Opens a storage with the specified name that is stored alongside this
+    /// storage (and creates it if none exists), such as the hint of a store.
+    ///
+    /// By default, this is equivalent to [`Storage::open()`], but storages can
+    /// use it to store related storages in the same location.
+    async fn open_sibling(&self, name: &str) -> Result<Self> {
+        Self::open(name).await
+    }
+
+    /// Deletes a storage that is stored alongside this storage, see
+    /// [`Storage::open_sibling()`].
+    async fn purge_sibling(&self, name: &str) -> Result<()> {
+        Self::purge(name).await
+    }
+
+    /// Opens a storage that is stored alongside this storage for reading only,
+    /// but never creates it, see [`Storage::open_sibling()`].
+    ///
+    /// Returns `None` if no such storage exists. By default, this always
+    /// returns `None`, for storages that cannot tell whether a sibling exists
+    /// without creating it.
+    async fn open_existing_sibling(&self, _name: &str) -> Result<Option<Self>> {
+        Ok(None)
+    }
+
     /// Returns the name of the storage.
     fn name(&self) -> &str;
 
@@ -107,12 +136,98 @@ pub trait Storage: Sized {
     /// merged) storage again, until the next merge is started.
     async fn stop_merge(&mut self) -> Result<()>;
 
+    /// Pauses merge mode.
+    ///
+    /// While merge mode is paused, all writes go to the current storage again
+    /// (and are immediately visible to reads), but the temporary merge storage
+    /// keeps all of its writes so that the merge can later be resumed. This
+    /// allows unrelated writes to be interleaved with a long running merge.
+    /// Does nothing if the storage is not in merge mode.
+    ///
+    /// Does nothing by default, storages that fork writes in merge mode need to
+    /// override this method (and [`Storage::resume_merge()`]) to support
+    /// incremental merges.
+    async fn pause_merge(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Resumes a paused merge mode, so that writes are "forked" again and go
+    /// to the temporary merge storage.
+    ///
+    /// Does nothing if the storage is not in merge mode or if merge mode is not
+    /// paused. Does nothing by default, see [`Storage::pause_merge()`].
+    async fn resume_merge(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Aborts merge mode, discarding all writes to the temporary merge storage.
+    ///
+    /// After an abort, all operations read from and write to the current
+    /// (unmerged) storage again, as if the merge had never been started. Does
+    /// nothing if the storage is not in merge mode. Does nothing by default,
+    /// see [`Storage::pause_merge()`].
+    async fn abort_merge(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Returns a read-only view of the contents of the storage that can be read
+    /// without exclusive access to the storage, or `None` if the storage does
+    /// not support views.
+    ///
+    /// The view covers a prefix of the storage, which does not necessarily
+    /// include the most recent writes. Storages can refuse to truncate their
+    /// contents below the length of a view that is still in use.
+    ///
+    /// Only file storages compiled with the "mmap" feature support views.
+    fn view(&mut self) -> Option<View> {
+        None
+    }
+
     /// Checks whether the storage is empty.
     fn is_empty(&self) -> bool {
         self.len() == 0
     }
 }
 
+/// A cheaply cloneable, read-only view of the contents of a storage (such as a
+/// memory-mapped file), see [`Storage::view()`].
+///
+/// The view does not change when the storage is written to, all bytes appended
+/// after the view was created are not part of the view.
+#[derive(Clone)]
+pub struct View(Arc<dyn AsRef<[u8]> + Send + Sync>);
+
+impl View {
+    /// Creates a view of the bytes.
+    pub fn new(bytes: impl AsRef<[u8]> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(bytes))
+    }
+
+    /// Returns the length of the view in bytes.
+    pub fn len(&self) -> u64 {
+        self.0.as_ref().as_ref().len() as u64
+    }
+
+    /// Checks whether the view is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reads the specified number of bytes starting at the specified offset.
+    ///
+    /// Behaves like [`Storage::read()`], if the view ends before the expected
+    /// number of bytes could be read, the rest of the resulting bytes will all
+    /// be 0.
+    pub fn read(&self, offset: u64, bytes: u32) -> Vec<u8> {
+        let contents = self.0.as_ref().as_ref();
+        let mut buf = vec![0; bytes as usize];
+        let start = min(offset, self.len()) as usize;
+        let end = min(offset + bytes as u64, self.len()) as usize;
+        buf[..end - start].copy_from_slice(&contents[start..end]);
+        buf
+    }
+}
+
 /// The error type for storage operations (wraps [`std::io::Error`]).
 #[derive(Debug)]
 pub enum Error {
@@ -127,6 +242,19 @@ pub enum Error {
         /// The maximum length and thus the maximum possible offset.
         max_length: u64,
     },
+    /// Caused by writing to a storage that was opened as read-only.
+    ReadOnlyError,
+    /// Caused by opening a storage whose lock is held by another storage, with
+    /// the path of the lock file.
+    LockError(String),
+    /// Caused by truncating a storage below the length of a view that is still
+    /// in use, see [`Storage::view()`].
+    ViewInUse {
+        /// The offset that the storage should have been truncated to.
+        offset: u64,
+        /// The length of the longest view that is still in use.
+        view_length: u64,
+    },
 }
 
 impl From<io::Error> for Error {
@@ -212,11 +340,13 @@ macro_rules! run {
                 .unwrap();
             let name = format!("{}_{}", file, line!());
             assemblage_kv::storage::purge(&name).await?;
+            assemblage_kv::storage::purge(assemblage_kv::hint_name(&name)).await?;
 
             let mut $storage = assemblage_kv::storage::open(&name).await?;
             let ret = $b;
 
             assemblage_kv::storage::purge(&name).await?;
+            assemblage_kv::storage::purge(assemblage_kv::hint_name(&name)).await?;
             ret
         })
     };
@@ -237,11 +367,13 @@ macro_rules! test {
             let file = std::path::Path::new(file!()).file_stem().unwrap().to_str().unwrap();
             let name = format!("{}_{}", file, line!());
             assemblage_kv::storage::purge(&name).await?;
+            assemblage_kv::storage::purge(assemblage_kv::hint_name(&name)).await?;
 
             let mut $storage = assemblage_kv::storage::open(&name).await?;
             $test
 
             assemblage_kv::storage::purge(&name).await?;
+            assemblage_kv::storage::purge(assemblage_kv::hint_name(&name)).await?;
             Ok(())
         });
     };
@@ -1,49 +1,286 @@
 //! A storage backend for stores backed by async file IO.
 #![cfg(not(target_arch = "wasm32"))]
 
-use super::{Error, Result, Storage};
+use super::{Error, Result, Storage, View};
 
 use async_trait::async_trait;
-use std::{cmp::min, convert::TryInto, io, path::Path};
+use fs2::FileExt;
+use std::{
+    cmp::min,
+    convert::TryInto,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Weak,
+    time::{Duration, Instant},
+};
 use tokio::fs::{remove_file, rename, File, OpenOptions};
 use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 
 /// A storage backend for stores backed by a file.
+///
+/// The file is named after the storage (with the extension `.aeon`) and is
+/// located in the current working directory, unless the storage is opened with
+/// [`FileStorage::open_at()`].
 pub struct FileStorage {
     name: String,
+    dir: PathBuf,
+    options: FileOptions,
+    // only held to keep the (advisory) lock until the storage is dropped:
+    _lock: Option<fs::File>,
     len_read: u64,
     len_write: u64,
     file: File,
     merge_file: Option<File>,
+    is_merge_paused: bool,
+    durability: Durability,
+    last_sync: Instant,
+    is_synced: bool,
+    mmap: Option<View>,
+    // the lengths of all mappings that were handed out as views and might
+    // still be in use, which must not be truncated:
+    views: Vec<(u64, WeakView)>,
 }
 
-#[async_trait(?Send)]
-impl Storage for FileStorage {
-    async fn open<'a>(name: impl Into<String> + 'a) -> Result<Self> {
+type WeakView = Weak<dyn AsRef<[u8]> + Send + Sync>;
+
+/// Decides how often a [`FileStorage`] syncs its writes to disk when it is
+/// flushed (which happens after every committed transaction).
+///
+/// All writes are handed to the OS immediately, so that they survive a crash
+/// of the process. Only syncing them to disk (so that they also survive a
+/// crash of the OS or a power loss) is subject to the durability mode.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum Durability {
+    /// Syncs to disk on every flush, so that every committed transaction is
+    /// durable. This is the default.
+    #[default]
+    EveryCommit,
+    /// Syncs to disk on a flush only if the last sync was at least the
+    /// specified number of milliseconds ago, so that transactions committed
+    /// since the last sync can be lost.
+    ///
+    /// Since syncs only happen during flushes, the interval only bounds the
+    /// loss while transactions continue to be committed. Writes that are still
+    /// unsynced when the storage is dropped are synced before it is closed.
+    Periodic(u64),
+    /// Never syncs to disk explicitly and leaves it up to the OS.
+    Never,
+}
+
+/// Options that control where and how a [`FileStorage`] is opened, see
+/// [`FileStorage::open_at()`].
+///
+/// The default options open (or create) a read-write file with the extension
+/// `.aeon`, without locking it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileOptions {
+    /// The extension of the file (without the leading dot).
+    pub extension: String,
+    /// Decides whether the file is created, opened or both, see [`OpenMode`].
+    pub mode: OpenMode,
+    /// Acquires an advisory lock on a lock file next to the storage file while
+    /// the storage is open, so that no other storage (in the same or in another
+    /// process) can open the same file with a lock and append to it at the
+    /// same time. Ignored for read-only storages.
+    pub lock: bool,
+}
+
+impl Default for FileOptions {
+    fn default() -> Self {
+        Self {
+            extension: String::from("aeon"),
+            mode: OpenMode::default(),
+            lock: false,
+        }
+    }
+}
+
+/// Decides whether a [`FileStorage`] can create a new file or open an existing
+/// one, see [`FileOptions`].
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum OpenMode {
+    /// Opens the file if it exists, otherwise creates it. This is the default.
+    #[default]
+    CreateOrOpen,
+    /// Creates a new file and fails if the file already exists.
+    CreateNew,
+    /// Opens an existing file and fails if the file does not exist.
+    OpenExisting,
+    /// Opens an existing file as read-only, all writes will fail with
+    /// [`Error::ReadOnlyError`].
+    ReadOnly,
+}
+
+impl FileStorage {
+    /// Opens the file storage with the specified name in the specified
+    /// directory, using the specified options, see [`FileOptions`].
+    ///
+    /// [`Storage::open()`] is equivalent to opening the storage in the current
+    /// working directory with the default options.
+    pub async fn open_at(
+        dir: impl AsRef<Path>,
+        name: impl Into<String>,
+        options: FileOptions,
+    ) -> Result<Self> {
         let name = name.into();
-        let file = OpenOptions::new()
-            .read(true)
-            .append(true)
-            .create(true)
-            .open(file_name(&name))
-            .await?;
+        let dir = dir.as_ref().to_path_buf();
+        let file_name = file_name(&name, &options.extension);
+        let lock = if options.lock && options.mode != OpenMode::ReadOnly {
+            let path = dir.join(lock_file_name(&file_name));
+            let lock = fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)?;
+            if lock.try_lock_exclusive().is_err() {
+                return Err(Error::LockError(path.to_string_lossy().into_owned()));
+            }
+            Some(lock)
+        } else {
+            None
+        };
+        let mut open_options = OpenOptions::new();
+        open_options.read(true);
+        match options.mode {
+            OpenMode::CreateOrOpen => open_options.append(true).create(true),
+            OpenMode::CreateNew => open_options.append(true).create_new(true),
+            OpenMode::OpenExisting => open_options.append(true),
+            OpenMode::ReadOnly => &mut open_options,
+        };
+        let file = open_options.open(dir.join(&file_name)).await?;
         let file_length = file.metadata().await?.len();
         Ok(Self {
             name,
+            dir,
+            options,
+            _lock: lock,
             len_read: file_length,
             len_write: file_length,
             file,
             merge_file: None,
+            is_merge_paused: false,
+            durability: Durability::default(),
+            last_sync: Instant::now(),
+            is_synced: true,
+            mmap: None,
+            views: Vec::new(),
         })
     }
 
-    async fn purge<'a>(name: impl Into<String> + 'a) -> Result<()> {
-        let path = name.into() + ".aeon";
-        if Path::new(&path).exists() {
+    /// Deletes the file storage with the specified name (and its lock file) in
+    /// the specified directory, using the extension of the specified options.
+    ///
+    /// Fails with an [`Error::LockError`] if the storage is still locked by a
+    /// storage that is open, in which case nothing is deleted.
+    pub async fn purge_at(
+        dir: impl AsRef<Path>,
+        name: impl Into<String>,
+        options: &FileOptions,
+    ) -> Result<()> {
+        let file_name = file_name(&name.into(), &options.extension);
+        let lock_path = dir.as_ref().join(lock_file_name(&file_name));
+        // the lock is held until both files are deleted, so that no other
+        // storage can open and lock the file in the meantime:
+        let lock = if lock_path.exists() {
+            let lock = fs::OpenOptions::new().write(true).open(&lock_path)?;
+            if lock.try_lock_exclusive().is_err() {
+                return Err(Error::LockError(lock_path.to_string_lossy().into_owned()));
+            }
+            Some(lock)
+        } else {
+            None
+        };
+        let path = dir.as_ref().join(file_name);
+        if path.exists() {
             remove_file(&path).await?;
         }
+        if lock.is_some() {
+            remove_file(&lock_path).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the path of the file that holds the contents of the storage.
+    pub fn path(&self) -> PathBuf {
+        self.dir
+            .join(file_name(&self.name, &self.options.extension))
+    }
+
+    /// Returns the options that the storage was opened with.
+    pub fn options(&self) -> &FileOptions {
+        &self.options
+    }
+
+    /// Sets the durability mode of the storage, see [`Durability`].
+    pub fn with_durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// Returns the durability mode of the storage.
+    pub fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.options.mode == OpenMode::ReadOnly {
+            Err(Error::ReadOnlyError)
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn sync(&mut self) -> Result<()> {
+        self.file.sync_data().await?;
+        self.last_sync = Instant::now();
+        self.is_synced = true;
         Ok(())
     }
+}
+
+#[async_trait(?Send)]
+impl Storage for FileStorage {
+    async fn open<'a>(name: impl Into<String> + 'a) -> Result<Self> {
+        Self::open_at(".", name, FileOptions::default()).await
+    }
+
+    async fn purge<'a>(name: impl Into<String> + 'a) -> Result<()> {
+        Self::purge_at(".", name, &FileOptions::default()).await
+    }
+
+    async fn open_sibling(&self, name: &str) -> Result<Self> {
+        let mode = match self.options.mode {
+            OpenMode::ReadOnly => OpenMode::ReadOnly,
+            _ => OpenMode::CreateOrOpen,
+        };
+        let options = FileOptions {
+            mode,
+            lock: false,
+            ..self.options.clone()
+        };
+        Self::open_at(&self.dir, name, options).await
+    }
+
+    async fn purge_sibling(&self, name: &str) -> Result<()> {
+        self.check_writable()?;
+        Self::purge_at(&self.dir, name, &self.options).await
+    }
+
+    async fn open_existing_sibling(&self, name: &str) -> Result<Option<Self>> {
+        if !self
+            .dir
+            .join(file_name(name, &self.options.extension))
+            .exists()
+        {
+            return Ok(None);
+        }
+        let options = FileOptions {
+            mode: OpenMode::ReadOnly,
+            lock: false,
+            ..self.options.clone()
+        };
+        Ok(Some(Self::open_at(&self.dir, name, options).await?))
+    }
 
     fn name(&self) -> &str {
         &self.name
@@ -54,8 +291,15 @@ impl Storage for FileStorage {
     }
 
     async fn read(&mut self, offset: u64, bytes: u32) -> Result<Vec<u8>> {
+        match self.view() {
+            Some(view) if offset + bytes as u64 <= view.len() => {
+                return Ok(view.read(offset, bytes));
+            }
+            // reads beyond the mapping fall back to regular file IO
+            _ => {}
+        }
         let mut buf = vec![0; bytes.try_into().unwrap()];
-        let bytes_to_read = min(bytes, (self.len_read - offset) as u32);
+        let bytes_to_read = min(bytes as u64, self.len_read.saturating_sub(offset)) as u32;
         self.file.seek(io::SeekFrom::Start(offset)).await?;
         self.file
             .read_exact(&mut buf[..bytes_to_read as usize])
@@ -64,65 +308,174 @@ impl Storage for FileStorage {
     }
 
     async fn write(&mut self, buf: &[u8]) -> Result<u64> {
-        let file = self.merge_file.as_mut().unwrap_or(&mut self.file);
-        let offset = self.len_write;
+        self.check_writable()?;
+        let (file, len) = match (self.merge_file.as_mut(), self.is_merge_paused) {
+            (Some(merge_file), false) => (merge_file, &mut self.len_write),
+            _ => (&mut self.file, &mut self.len_read),
+        };
+        let offset = *len;
         file.seek(io::SeekFrom::Start(offset)).await?;
         file.write_all(buf).await?;
         file.flush().await?;
-        self.len_write += buf.len() as u64;
+        *len += buf.len() as u64;
+        self.is_synced = false;
         if self.merge_file.is_none() {
-            self.len_read += buf.len() as u64;
+            self.len_write = self.len_read;
         }
         Ok(offset)
     }
 
     async fn truncate(&mut self, offset: u64) -> Result<()> {
+        self.check_writable()?;
         let max_length = self.len();
+        self.mmap = None;
+        self.views.retain(|(_, view)| view.strong_count() > 0);
+        let mapped = self.views.iter().map(|(len, _)| *len).max().unwrap_or(0);
         if offset > max_length {
             Err(Error::OffsetError { offset, max_length })
+        } else if offset < mapped {
+            // shrinking a file below a mapping that is still in use would crash
+            // the process as soon as the view is read beyond the new length:
+            Err(Error::ViewInUse {
+                offset,
+                view_length: mapped,
+            })
         } else {
             self.file.set_len(offset).await?;
-            self.len_write = offset;
+            self.len_read = offset;
             if self.merge_file.is_none() {
-                self.len_read = offset;
+                self.len_write = offset;
             }
-            self.flush().await?;
-            Ok(())
+            self.file.flush().await?;
+            self.sync().await
         }
     }
 
     async fn flush(&mut self) -> Result<()> {
         self.file.flush().await?;
-        self.file.sync_all().await?;
-        Ok(())
+        if self.options.mode == OpenMode::ReadOnly {
+            return Ok(());
+        }
+        match self.durability {
+            Durability::EveryCommit => self.sync().await,
+            Durability::Periodic(millis) => {
+                if self.last_sync.elapsed() >= Duration::from_millis(millis) {
+                    self.sync().await?;
+                }
+                Ok(())
+            }
+            Durability::Never => Ok(()),
+        }
     }
 
     async fn start_merge(&mut self) -> Result<()> {
+        self.check_writable()?;
         let file = OpenOptions::new()
             .read(true)
             .append(true)
             .create(true)
-            .open(merge_file_name(&self.name))
+            .open(self.merge_path())
             .await?;
+        // a merge file left over from an interrupted merge must be discarded:
+        file.set_len(0).await?;
         self.merge_file = Some(file);
         self.len_write = 0;
+        self.is_merge_paused = false;
         Ok(())
     }
 
     async fn stop_merge(&mut self) -> Result<()> {
-        let src = merge_file_name(&self.name);
-        let dst = file_name(&self.name);
-        rename(src, dst).await?;
+        // the merged file replaces the original, so it must be on disk first:
+        if let Some(merge_file) = self.merge_file.as_mut() {
+            merge_file.flush().await?;
+            merge_file.sync_all().await?;
+        }
+        rename(self.merge_path(), self.path()).await?;
         self.file = self.merge_file.take().unwrap();
+        // views of the pre-merge file still map the old (now unlinked) file:
+        self.mmap = None;
+        self.views.clear();
         self.len_read = self.len_write;
+        self.is_merge_paused = false;
+        Ok(())
+    }
+
+    async fn pause_merge(&mut self) -> Result<()> {
+        self.is_merge_paused = self.merge_file.is_some();
+        Ok(())
+    }
+
+    async fn resume_merge(&mut self) -> Result<()> {
+        self.is_merge_paused = false;
         Ok(())
     }
+
+    async fn abort_merge(&mut self) -> Result<()> {
+        self.is_merge_paused = false;
+        self.len_write = self.len_read;
+        if let Some(merge_file) = self.merge_file.take() {
+            drop(merge_file);
+            remove_file(self.merge_path()).await?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "mmap")]
+    #[allow(unsafe_code)]
+    fn view(&mut self) -> Option<View> {
+        if self.len_read == 0 {
+            return None;
+        }
+        // the file is only remapped once it has grown to twice the length of
+        // the current mapping, so that appending to the file does not remap it
+        // on every read, bytes after the mapping are read using regular file IO
+        let mapped = self.mmap.as_ref().map_or(0, View::len);
+        if self.len_read >= 2 * mapped {
+            // Safety: the file is only ever appended to (and the mapping only
+            // covers bytes that have already been written), truncating the file
+            // below the length of a mapping that is still in use fails and a
+            // merge replaces the file instead of modifying it. The file must
+            // not be modified by other processes while it is mapped.
+            let mmap = unsafe {
+                memmap2::MmapOptions::new()
+                    .len(self.len_read as usize)
+                    .map(&self.file)
+            };
+            // if the file cannot be mapped, reads fall back to regular file IO
+            self.mmap = mmap.ok().map(View::new);
+            if let Some(view) = self.mmap.as_ref() {
+                self.views.retain(|(_, view)| view.strong_count() > 0);
+                self.views
+                    .push((view.len(), std::sync::Arc::downgrade(&view.0)));
+            }
+        }
+        self.mmap.clone()
+    }
+}
+
+impl FileStorage {
+    fn merge_path(&self) -> PathBuf {
+        let file_name = file_name(&self.name, &self.options.extension);
+        self.dir.join(file_name + ".merged")
+    }
+}
+
+impl Drop for FileStorage {
+    fn drop(&mut self) {
+        // periodic syncs only happen during flushes, so the last writes might
+        // never have been synced (errors cannot be reported during a drop):
+        if matches!(self.durability, Durability::Periodic(_)) && !self.is_synced {
+            if let Ok(file) = fs::OpenOptions::new().append(true).open(self.path()) {
+                let _ = file.sync_data();
+            }
+        }
+    }
 }
 
-fn file_name(name: &str) -> String {
-    String::from(name) + ".aeon"
+fn file_name(name: &str, extension: &str) -> String {
+    format!("{}.{}", name, extension)
 }
 
-fn merge_file_name(name: &str) -> String {
-    String::from(name) + ".aeon.merged"
+fn lock_file_name(file_name: &str) -> String {
+    String::from(file_name) + ".lock"
 }
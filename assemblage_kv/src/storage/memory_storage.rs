@@ -8,6 +8,7 @@ pub struct MemoryStorage {
     name: String,
     bytes: Vec<u8>,
     bytes_for_merge: Option<Vec<u8>>,
+    is_merge_paused: bool,
 }
 
 impl MemoryStorage {
@@ -23,6 +24,7 @@ impl MemoryStorage {
             name: "".to_string(),
             bytes: bytes.into(),
             bytes_for_merge: None,
+            is_merge_paused: false,
         }
     }
 
@@ -45,6 +47,7 @@ impl Storage for MemoryStorage {
             name: name.into(),
             bytes: Vec::new(),
             bytes_for_merge: None,
+            is_merge_paused: false,
         })
     }
 
@@ -73,7 +76,10 @@ impl Storage for MemoryStorage {
     }
 
     async fn write(&mut self, buffer: &[u8]) -> Result<u64> {
-        let bytes = self.bytes_for_merge.as_mut().unwrap_or(&mut self.bytes);
+        let bytes = match (self.bytes_for_merge.as_mut(), self.is_merge_paused) {
+            (Some(bytes_for_merge), false) => bytes_for_merge,
+            _ => &mut self.bytes,
+        };
         bytes.extend(buffer);
         Ok((bytes.len() - buffer.len()) as u64)
     }
@@ -89,11 +95,29 @@ impl Storage for MemoryStorage {
 
     async fn start_merge(&mut self) -> Result<()> {
         self.bytes_for_merge = Some(Vec::new());
+        self.is_merge_paused = false;
         Ok(())
     }
 
     async fn stop_merge(&mut self) -> Result<()> {
         self.bytes = self.bytes_for_merge.take().unwrap_or_default();
+        self.is_merge_paused = false;
+        Ok(())
+    }
+
+    async fn pause_merge(&mut self) -> Result<()> {
+        self.is_merge_paused = self.bytes_for_merge.is_some();
+        Ok(())
+    }
+
+    async fn resume_merge(&mut self) -> Result<()> {
+        self.is_merge_paused = false;
+        Ok(())
+    }
+
+    async fn abort_merge(&mut self) -> Result<()> {
+        self.bytes_for_merge = None;
+        self.is_merge_paused = false;
         Ok(())
     }
 }
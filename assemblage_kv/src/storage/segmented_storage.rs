@@ -0,0 +1,466 @@
+//! A storage backend for stores backed by a sequence of segment files.
+#![cfg(not(target_arch = "wasm32"))]
+
+use super::{
+    file_storage::{FileOptions, FileStorage, OpenMode},
+    Error, Result, Storage,
+};
+
+use async_trait::async_trait;
+use std::{
+    cmp::{max, min},
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+use tokio::{
+    fs::{read_dir, read_to_string, remove_file, rename, File},
+    io::AsyncWriteExt,
+};
+
+/// A storage backend for stores backed by a sequence of segment files.
+///
+/// All segments together form a single contiguous storage, each byte is
+/// addressed by its segment and its offset inside of the segment, which
+/// corresponds to an offset in the storage after adding the lengths of all the
+/// segments before it, see [`SegmentedStorage::locate()`]. New bytes are
+/// appended to the last segment until it reaches the segment size, after which
+/// a new segment is started. A single write is never split across segments, so
+/// that every transaction of a store is contained in a single segment and the
+/// segments can be compacted independently of each other, see
+/// [`crate::KvStore::compact()`].
+///
+/// Segments are files named after the storage and their id (with the
+/// extension `.seg`), the ids of the current segments are listed in a file
+/// with the extension `.segments`.
+pub struct SegmentedStorage {
+    name: String,
+    dir: PathBuf,
+    options: SegmentOptions,
+    segments: Vec<Segment>,
+    merge_segments: Option<Vec<Segment>>,
+    is_merge_paused: bool,
+    next_id: u32,
+}
+
+struct Segment {
+    id: u32,
+    file: FileStorage,
+}
+
+/// Options that control where and how a [`SegmentedStorage`] is opened, see
+/// [`SegmentedStorage::open_at()`].
+///
+/// The default options open (or create) a read-write storage with segments of
+/// 64 MiB.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentOptions {
+    /// The size in bytes after which no more writes are appended to a segment
+    /// and a new segment is started instead. Segments can be larger than this
+    /// size, because a single write is never split across segments.
+    pub segment_size: u64,
+    /// Decides whether the storage is created, opened or both, see
+    /// [`OpenMode`].
+    pub mode: OpenMode,
+}
+
+impl Default for SegmentOptions {
+    fn default() -> Self {
+        Self {
+            segment_size: 64 << 20,
+            mode: OpenMode::default(),
+        }
+    }
+}
+
+impl SegmentedStorage {
+    /// Opens the segmented storage with the specified name in the specified
+    /// directory, using the specified options, see [`SegmentOptions`].
+    ///
+    /// [`Storage::open()`] is equivalent to opening the storage in the current
+    /// working directory with the default options. Segment files that are not
+    /// listed as current segments (left over from an interrupted merge or
+    /// compaction) are deleted, unless the storage is opened as read-only.
+    pub async fn open_at(
+        dir: impl AsRef<Path>,
+        name: impl Into<String>,
+        options: SegmentOptions,
+    ) -> Result<Self> {
+        let name = name.into();
+        let dir = dir.as_ref().to_path_buf();
+        let manifest_path = dir.join(manifest_name(&name));
+        let ids = match (read_to_string(&manifest_path).await, options.mode) {
+            (Ok(_), OpenMode::CreateNew) => {
+                let e = io::Error::new(io::ErrorKind::AlreadyExists, "storage already exists");
+                return Err(Error::IoError(e));
+            }
+            (Ok(manifest), _) => parse_manifest(&manifest)?,
+            (Err(e), OpenMode::OpenExisting) | (Err(e), OpenMode::ReadOnly) => {
+                return Err(Error::IoError(e))
+            }
+            (Err(_), _) => {
+                write_manifest(&dir, &name, &[]).await?;
+                Vec::new()
+            }
+        };
+        if options.mode != OpenMode::ReadOnly {
+            for (id, path) in segment_files(&dir, &name).await? {
+                if !ids.contains(&id) || path.extension() != Some("seg".as_ref()) {
+                    remove_file(path).await?;
+                }
+            }
+        }
+        let mut storage = Self {
+            name,
+            dir,
+            options,
+            segments: Vec::with_capacity(ids.len()),
+            merge_segments: None,
+            is_merge_paused: false,
+            next_id: ids.iter().max().map_or(1, |id| id + 1),
+        };
+        for id in ids {
+            let file = storage.open_segment(id, OpenMode::OpenExisting).await?;
+            storage.segments.push(Segment { id, file });
+        }
+        Ok(storage)
+    }
+
+    /// Deletes the segmented storage with the specified name (and all of its
+    /// segments) in the specified directory.
+    pub async fn purge_at(dir: impl AsRef<Path>, name: impl Into<String>) -> Result<()> {
+        let name = name.into();
+        let dir = dir.as_ref();
+        for (_, path) in segment_files(dir, &name).await? {
+            remove_file(path).await?;
+        }
+        for path in [manifest_name(&name), manifest_name(&name) + ".tmp"] {
+            let path = dir.join(path);
+            if path.exists() {
+                remove_file(&path).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the options that the storage was opened with.
+    pub fn options(&self) -> &SegmentOptions {
+        &self.options
+    }
+
+    /// Returns the ranges of offsets covered by each of the current segments,
+    /// ordered by their offsets.
+    pub fn segment_ranges(&self) -> Vec<Range<u64>> {
+        let mut base = 0;
+        self.segments
+            .iter()
+            .map(|segment| {
+                let start = base;
+                base += segment.file.len();
+                start..base
+            })
+            .collect()
+    }
+
+    /// Returns the id of the segment that contains the specified offset and
+    /// the offset relative to the start of the segment, or `None` if the offset
+    /// is beyond the end of the storage.
+    pub fn locate(&self, offset: u64) -> Option<(u32, u64)> {
+        self.segment_ranges()
+            .into_iter()
+            .zip(self.segments.iter())
+            .find(|(range, _)| range.contains(&offset))
+            .map(|(range, segment)| (segment.id, offset - range.start))
+    }
+
+    /// Replaces the contents of the segment at the specified index (in the
+    /// order of [`SegmentedStorage::segment_ranges()`]) with the bytes.
+    ///
+    /// The bytes are written to a temporary file that then replaces the
+    /// segment file, so that the segment is either fully replaced or not at
+    /// all. All offsets after the replaced segment are shifted by the
+    /// difference in length.
+    pub(crate) async fn replace_segment(&mut self, index: usize, bytes: &[u8]) -> Result<()> {
+        self.check_writable()?;
+        let id = self.segments[index].id;
+        let path = self.segments[index].file.path();
+        let mut tmp_path = path.clone().into_os_string();
+        tmp_path.push(".compacted");
+        let mut file = File::create(&tmp_path).await?;
+        file.write_all(bytes).await?;
+        file.sync_all().await?;
+        drop(file);
+        rename(&tmp_path, &path).await?;
+        self.segments[index].file = self.open_segment(id, OpenMode::OpenExisting).await?;
+        Ok(())
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.options.mode == OpenMode::ReadOnly {
+            Err(Error::ReadOnlyError)
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn open_segment(&self, id: u32, mode: OpenMode) -> Result<FileStorage> {
+        let mode = match self.options.mode {
+            OpenMode::ReadOnly => OpenMode::ReadOnly,
+            _ => mode,
+        };
+        let options = FileOptions {
+            extension: String::from("seg"),
+            mode,
+            lock: false,
+        };
+        FileStorage::open_at(&self.dir, segment_name(&self.name, id), options).await
+    }
+
+    async fn write_manifest(&self) -> Result<()> {
+        let ids: Vec<u32> = self.segments.iter().map(|s| s.id).collect();
+        write_manifest(&self.dir, &self.name, &ids).await
+    }
+}
+
+#[async_trait(?Send)]
+impl Storage for SegmentedStorage {
+    async fn open<'a>(name: impl Into<String> + 'a) -> Result<Self> {
+        Self::open_at(".", name, SegmentOptions::default()).await
+    }
+
+    async fn purge<'a>(name: impl Into<String> + 'a) -> Result<()> {
+        Self::purge_at(".", name).await
+    }
+
+    async fn open_sibling(&self, name: &str) -> Result<Self> {
+        let mode = match self.options.mode {
+            OpenMode::ReadOnly => OpenMode::ReadOnly,
+            _ => OpenMode::CreateOrOpen,
+        };
+        let options = SegmentOptions {
+            mode,
+            ..self.options.clone()
+        };
+        Self::open_at(&self.dir, name, options).await
+    }
+
+    async fn purge_sibling(&self, name: &str) -> Result<()> {
+        self.check_writable()?;
+        Self::purge_at(&self.dir, name).await
+    }
+
+    async fn open_existing_sibling(&self, name: &str) -> Result<Option<Self>> {
+        if !self.dir.join(manifest_name(name)).exists() {
+            return Ok(None);
+        }
+        let options = SegmentOptions {
+            mode: OpenMode::ReadOnly,
+            ..self.options.clone()
+        };
+        Ok(Some(Self::open_at(&self.dir, name, options).await?))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn len(&self) -> u64 {
+        self.segments.iter().map(|segment| segment.file.len()).sum()
+    }
+
+    async fn read(&mut self, offset: u64, bytes: u32) -> Result<Vec<u8>> {
+        let mut buf = vec![0; bytes as usize];
+        let end = min(offset + bytes as u64, self.len());
+        let mut base = 0;
+        for segment in self.segments.iter_mut() {
+            let segment_end = base + segment.file.len();
+            if offset < segment_end && base < end {
+                let start = max(offset, base);
+                let stop = min(end, segment_end);
+                let read = segment.file.read(start - base, (stop - start) as u32);
+                buf[(start - offset) as usize..(stop - offset) as usize]
+                    .copy_from_slice(&read.await?);
+            }
+            base = segment_end;
+            if base >= end {
+                break;
+            }
+        }
+        Ok(buf)
+    }
+
+    async fn write(&mut self, buf: &[u8]) -> Result<u64> {
+        self.check_writable()?;
+        let is_merging = self.merge_segments.is_some() && !self.is_merge_paused;
+        let segments = match self.merge_segments.as_ref() {
+            Some(merge_segments) if is_merging => merge_segments,
+            _ => &self.segments,
+        };
+        let is_full = match segments.last() {
+            Some(segment) => segment.file.len() >= self.options.segment_size,
+            None => true,
+        };
+        if is_full && !buf.is_empty() {
+            let id = self.next_id;
+            self.next_id += 1;
+            let file = self.open_segment(id, OpenMode::CreateNew).await?;
+            let segments = match self.merge_segments.as_mut() {
+                Some(merge_segments) if is_merging => merge_segments,
+                _ => &mut self.segments,
+            };
+            if let Some(segment) = segments.last_mut() {
+                segment.file.flush().await?;
+            }
+            segments.push(Segment { id, file });
+            if !is_merging {
+                self.write_manifest().await?;
+            }
+        }
+        let segments = match self.merge_segments.as_mut() {
+            Some(merge_segments) if is_merging => merge_segments,
+            _ => &mut self.segments,
+        };
+        match segments.split_last_mut() {
+            Some((segment, previous)) => {
+                let base: u64 = previous.iter().map(|segment| segment.file.len()).sum();
+                Ok(base + segment.file.write(buf).await?)
+            }
+            None => Ok(0),
+        }
+    }
+
+    async fn truncate(&mut self, offset: u64) -> Result<()> {
+        self.check_writable()?;
+        let max_length = self.len();
+        if offset > max_length {
+            return Err(Error::OffsetError { offset, max_length });
+        }
+        let ranges = self.segment_ranges();
+        let kept = ranges.iter().filter(|range| range.start < offset).count();
+        let removed = self.segments.split_off(kept);
+        if let Some(segment) = self.segments.last_mut() {
+            segment
+                .file
+                .truncate(offset - ranges[kept - 1].start)
+                .await?;
+        }
+        self.write_manifest().await?;
+        purge_segments(removed).await
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        if let Some(segment) = self.segments.last_mut() {
+            segment.file.flush().await?;
+        }
+        if let Some(segment) = self.merge_segments.iter_mut().flatten().last() {
+            segment.file.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn start_merge(&mut self) -> Result<()> {
+        self.check_writable()?;
+        // merge segments left over from an unfinished merge must be discarded:
+        if let Some(merge_segments) = self.merge_segments.take() {
+            purge_segments(merge_segments).await?;
+        }
+        self.merge_segments = Some(Vec::new());
+        self.is_merge_paused = false;
+        Ok(())
+    }
+
+    async fn stop_merge(&mut self) -> Result<()> {
+        let mut merge_segments = self.merge_segments.take().unwrap_or_default();
+        // the merged segments replace the originals, so they must be on disk:
+        for segment in merge_segments.iter_mut() {
+            segment.file.flush().await?;
+        }
+        let segments = std::mem::replace(&mut self.segments, merge_segments);
+        self.write_manifest().await?;
+        self.is_merge_paused = false;
+        purge_segments(segments).await
+    }
+
+    async fn pause_merge(&mut self) -> Result<()> {
+        self.is_merge_paused = self.merge_segments.is_some();
+        Ok(())
+    }
+
+    async fn resume_merge(&mut self) -> Result<()> {
+        self.is_merge_paused = false;
+        Ok(())
+    }
+
+    async fn abort_merge(&mut self) -> Result<()> {
+        self.is_merge_paused = false;
+        match self.merge_segments.take() {
+            Some(merge_segments) => purge_segments(merge_segments).await,
+            None => Ok(()),
+        }
+    }
+}
+
+async fn purge_segments(segments: Vec<Segment>) -> Result<()> {
+    for segment in segments {
+        let path = segment.file.path();
+        drop(segment);
+        remove_file(path).await?;
+    }
+    Ok(())
+}
+
+fn segment_name(name: &str, id: u32) -> String {
+    format!("{}.{:06}", name, id)
+}
+
+fn manifest_name(name: &str) -> String {
+    format!("{}.segments", name)
+}
+
+fn parse_manifest(manifest: &str) -> Result<Vec<u32>> {
+    manifest
+        .split_whitespace()
+        .map(|id| {
+            id.parse().map_err(|_| {
+                let reason = format!("invalid segment id '{}'", id);
+                Error::IoError(io::Error::new(io::ErrorKind::InvalidData, reason))
+            })
+        })
+        .collect()
+}
+
+/// Atomically replaces the list of current segments by writing it to a
+/// temporary file first.
+async fn write_manifest(dir: &Path, name: &str, ids: &[u32]) -> Result<()> {
+    let path = dir.join(manifest_name(name));
+    let tmp_path = dir.join(manifest_name(name) + ".tmp");
+    let manifest: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+    let mut file = File::create(&tmp_path).await?;
+    file.write_all(manifest.join("\n").as_bytes()).await?;
+    file.sync_all().await?;
+    drop(file);
+    rename(tmp_path, path).await?;
+    Ok(())
+}
+
+/// Returns the ids and paths of all (current, stale or temporary) segment files
+/// of the storage with the specified name.
+async fn segment_files(dir: &Path, name: &str) -> Result<Vec<(u32, PathBuf)>> {
+    let prefix = format!("{}.", name);
+    let mut files = Vec::new();
+    let mut entries = read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name();
+        let id = file_name
+            .to_str()
+            .and_then(|file_name| file_name.strip_prefix(&prefix))
+            .and_then(|rest| rest.split_once('.'))
+            .filter(|(_, extension)| *extension == "seg" || *extension == "seg.compacted")
+            .filter(|(id, _)| !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|(id, _)| id.parse().ok());
+        if let Some(id) = id {
+            files.push((id, entry.path()));
+        }
+    }
+    Ok(files)
+}
@@ -33,6 +33,13 @@ impl ActiveStore {
             ActiveStore::B => CONTENT_STORE_B,
         }
     }
+
+    fn other(&self) -> Self {
+        match self {
+            ActiveStore::A => ActiveStore::B,
+            ActiveStore::B => ActiveStore::A,
+        }
+    }
 }
 
 impl From<wasm_bindgen::JsValue> for Error {
@@ -98,6 +105,7 @@ pub struct WebStorage {
     offsets: HashMap<u64, u64>,
     store_for_reads: ActiveStore,
     store_for_writes: ActiveStore,
+    is_merge_paused: bool,
 }
 
 impl WebStorage {
@@ -208,6 +216,7 @@ impl Storage for WebStorage {
             offsets,
             store_for_reads: active_store,
             store_for_writes: active_store,
+            is_merge_paused: false,
         })
     }
 
@@ -320,10 +329,13 @@ impl Storage for WebStorage {
             let k = JsValue::from_f64(block_offset as f64);
             AsyncIdbRequest::from(content.put_with_key(block, &k)?).await?;
         }
-        let meta = t.object_store(META_STORE)?;
-        let k = JsValue::from_str(META_FIELD_LENGTH);
-        let v = JsValue::from_f64(self.offset_buffer as f64);
-        AsyncIdbRequest::from(meta.put_with_key(&v, &k)?).await?;
+        // the length of the merge store is only persisted when the merge stops:
+        if self.store_for_writes as u64 == self.store_for_reads as u64 {
+            let meta = t.object_store(META_STORE)?;
+            let k = JsValue::from_str(META_FIELD_LENGTH);
+            let v = JsValue::from_f64(self.offset_buffer as f64);
+            AsyncIdbRequest::from(meta.put_with_key(&v, &k)?).await?;
+        }
         self.offsets
             .insert(self.store_for_writes as u64, self.offset_buffer);
         self.blocks.clear();
@@ -331,16 +343,15 @@ impl Storage for WebStorage {
     }
 
     async fn start_merge(&mut self) -> Result<()> {
-        self.store_for_writes = match self.store_for_writes {
-            ActiveStore::A => ActiveStore::B,
-            ActiveStore::B => ActiveStore::A,
-        };
+        self.resume_merge().await?;
+        self.store_for_writes = self.store_for_writes.other();
         self.offsets.insert(self.store_for_writes as u64, 0);
         self.offset_buffer = 0;
         Ok(())
     }
 
     async fn stop_merge(&mut self) -> Result<()> {
+        self.resume_merge().await?;
         self.flush().await?;
         self.store_for_reads = self.store_for_writes;
         self.offsets.insert(
@@ -362,4 +373,32 @@ impl Storage for WebStorage {
 
         Ok(())
     }
+
+    async fn pause_merge(&mut self) -> Result<()> {
+        if !self.is_merge_paused && self.store_for_reads as u64 != self.store_for_writes as u64 {
+            self.flush().await?;
+            self.store_for_writes = self.store_for_reads;
+            self.offset_buffer = self.offset_store(self.store_for_writes as u64);
+            self.is_merge_paused = true;
+        }
+        Ok(())
+    }
+
+    async fn resume_merge(&mut self) -> Result<()> {
+        if self.is_merge_paused {
+            self.flush().await?;
+            self.store_for_writes = self.store_for_reads.other();
+            self.offset_buffer = self.offset_store(self.store_for_writes as u64);
+            self.is_merge_paused = false;
+        }
+        Ok(())
+    }
+
+    async fn abort_merge(&mut self) -> Result<()> {
+        // a paused merge writes to the store used for reads, so the other
+        // store is simply left behind and overwritten by the next merge:
+        self.pause_merge().await?;
+        self.is_merge_paused = false;
+        Ok(())
+    }
 }
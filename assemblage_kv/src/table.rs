@@ -0,0 +1,223 @@
+//! Statically typed tables, which associate a slot of a store with a key and
+//! value type.
+//!
+//! All reads and writes of a [`Snapshot`] take a bare slot number and leave it
+//! to each call site to pick the right key and value types. A [`Table`] fixes
+//! the types of a slot once, where the table is declared, so that all reads and
+//! writes through the table are checked by the compiler. A [`TableRegistry`]
+//! ensures that no two tables of an application claim the same slot.
+use crate::{codec::Codec, storage::Storage, Error, Result, Snapshot, Version};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    any::{type_name, TypeId},
+    collections::BTreeMap,
+    fmt,
+    marker::PhantomData,
+    ops::RangeBounds,
+};
+
+/// A slot of a store whose keys and values are of type `K` and `V`.
+///
+/// Tables are usually declared as constants and only wrap the slot number, all
+/// of their methods are thin wrappers around the methods of a [`Snapshot`].
+pub struct Table<K, V> {
+    name: &'static str,
+    slot: u8,
+    types: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K, V> Table<K, V> {
+    /// Declares a table with the specified name that stores its keys and values
+    /// in the specified slot.
+    pub const fn new(name: &'static str, slot: u8) -> Self {
+        Self {
+            name,
+            slot,
+            types: PhantomData,
+        }
+    }
+
+    /// Returns the name of the table.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the slot of the table.
+    pub fn slot(&self) -> u8 {
+        self.slot
+    }
+}
+
+impl<K, V> Clone for Table<K, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K, V> Copy for Table<K, V> {}
+
+impl<K, V> fmt::Debug for Table<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Table")
+            .field("name", &self.name)
+            .field("slot", &self.slot)
+            .finish()
+    }
+}
+
+impl<K, V> Table<K, V>
+where
+    K: Serialize + DeserializeOwned,
+    V: Serialize + DeserializeOwned,
+{
+    /// Returns the latest value associated with the key, see [`Snapshot::get()`].
+    pub async fn get<S: Storage, C: Codec>(
+        &self,
+        snapshot: &Snapshot<'_, S, C>,
+        k: &K,
+    ) -> Result<Option<V>> {
+        snapshot.get(self.slot, k).await
+    }
+
+    /// Returns the latest _non-removed_ value associated with the key, see
+    /// [`Snapshot::get_unremoved()`].
+    pub async fn get_unremoved<S: Storage, C: Codec>(
+        &self,
+        snapshot: &Snapshot<'_, S, C>,
+        k: &K,
+    ) -> Result<Option<V>> {
+        snapshot.get_unremoved(self.slot, k).await
+    }
+
+    /// Returns the specified version of the value associated with the key, see
+    /// [`Snapshot::get_version()`].
+    pub async fn get_version<S: Storage, C: Codec>(
+        &self,
+        snapshot: &Snapshot<'_, S, C>,
+        k: &K,
+        version: Version,
+    ) -> Result<Option<V>> {
+        snapshot.get_version(self.slot, k, version).await
+    }
+
+    /// Returns all versions of the key, see [`Snapshot::versions()`].
+    pub async fn versions<S: Storage, C: Codec>(
+        &self,
+        snapshot: &Snapshot<'_, S, C>,
+        k: &K,
+    ) -> Result<Vec<Version>> {
+        snapshot.versions(self.slot, k).await
+    }
+
+    /// Returns all non-removed keys of the table, see [`Snapshot::keys()`].
+    pub async fn keys<S: Storage, C: Codec>(
+        &self,
+        snapshot: &Snapshot<'_, S, C>,
+    ) -> Result<Vec<K>> {
+        snapshot.keys(self.slot).await
+    }
+
+    /// Returns all non-removed key-value pairs whose keys fall inside the range,
+    /// see [`Snapshot::range()`].
+    pub async fn range<S: Storage, C: Codec, R: RangeBounds<K>>(
+        &self,
+        snapshot: &Snapshot<'_, S, C>,
+        range: R,
+    ) -> Result<Vec<(K, V)>> {
+        snapshot.range(self.slot, range).await
+    }
+
+    /// Inserts a key-value pair, see [`Snapshot::insert()`].
+    pub fn insert<S: Storage, C: Codec>(
+        &self,
+        snapshot: &mut Snapshot<'_, S, C>,
+        k: &K,
+        v: V,
+    ) -> Result<()> {
+        snapshot.insert(self.slot, k, v)
+    }
+
+    /// Removes the value associated with the key (and moves it to the trash),
+    /// see [`Snapshot::remove()`].
+    pub fn remove<S: Storage, C: Codec>(
+        &self,
+        snapshot: &mut Snapshot<'_, S, C>,
+        k: &K,
+    ) -> Result<()> {
+        snapshot.remove(self.slot, k)
+    }
+}
+
+/// A registry of tables, which rejects tables that claim a slot that is already
+/// claimed by another table, as well as tables that share the name of another
+/// table but differ in their slot, key type or value type.
+#[derive(Debug, Clone, Default)]
+pub struct TableRegistry {
+    tables: BTreeMap<u8, Registered>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Registered {
+    name: &'static str,
+    types: (TypeId, TypeId),
+    type_names: (&'static str, &'static str),
+}
+
+impl TableRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the table, failing with an [`Error::DuplicateSlot`] if another
+    /// table with a different name has already been registered for its slot,
+    /// or with an [`Error::ConflictingTable`] if a table with the same name but
+    /// a different slot, key type or value type has already been registered.
+    ///
+    /// Registering the same table twice has no effect.
+    pub fn register<K: 'static, V: 'static>(&mut self, table: &Table<K, V>) -> Result<()> {
+        let registered = Registered {
+            name: table.name,
+            types: (TypeId::of::<K>(), TypeId::of::<V>()),
+            type_names: (type_name::<K>(), type_name::<V>()),
+        };
+        for (slot, other) in self.tables.iter() {
+            if other.name != table.name {
+                continue;
+            }
+            let reason = if *slot != table.slot {
+                format!("registered for slot {}, not slot {}", slot, table.slot)
+            } else if other.types != registered.types {
+                format!(
+                    "registered as Table<{}, {}>, not Table<{}, {}>",
+                    other.type_names.0,
+                    other.type_names.1,
+                    registered.type_names.0,
+                    registered.type_names.1
+                )
+            } else {
+                return Ok(());
+            };
+            return Err(Error::ConflictingTable {
+                name: table.name.to_string(),
+                reason,
+            });
+        }
+        match self.tables.get(&table.slot) {
+            Some(other) => Err(Error::DuplicateSlot {
+                slot: table.slot,
+                registered: other.name.to_string(),
+                rejected: table.name.to_string(),
+            }),
+            None => {
+                self.tables.insert(table.slot, registered);
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the name of the table registered for the slot, if any.
+    pub fn get(&self, slot: u8) -> Option<&'static str> {
+        self.tables.get(&slot).map(|table| table.name)
+    }
+}
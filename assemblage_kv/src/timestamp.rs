@@ -1,4 +1,4 @@
-//! Timestamp utilities that run on both native and wasm targets.
+//! Timestamp and timer utilities that run on both native and wasm targets.
 use std::cmp::max;
 
 #[cfg(target_arch = "wasm32")]
@@ -36,3 +36,30 @@ pub fn timestamp_now() -> u64 {
 pub fn timestamp_now_monotonic(most_recent_timestamp: u64) -> u64 {
     max(most_recent_timestamp, timestamp_now())
 }
+
+/// Returns a strictly monotonically increasing timestamp that is the current
+/// time (in milliseconds since the Unix epoch) if current time > most recent
+/// timestamp, otherwise the most recent timestamp + 1. (This fn is used to
+/// ensure that two commits never share the same timestamp, even if they happen
+/// within the same millisecond.)
+pub fn timestamp_after(most_recent_timestamp: u64) -> u64 {
+    max(most_recent_timestamp.saturating_add(1), timestamp_now())
+}
+
+/// Waits for the specified number of milliseconds.
+#[cfg(target_arch = "wasm32")]
+pub async fn sleep(millis: u64) {
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        let window = web_sys::window().expect("could not get window for sleep");
+        window
+            .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, millis as i32)
+            .expect("could not set timeout for sleep");
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
+
+/// Waits for the specified number of milliseconds.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn sleep(millis: u64) {
+    tokio::time::sleep(time::Duration::from_millis(millis)).await;
+}
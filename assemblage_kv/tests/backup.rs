@@ -0,0 +1,84 @@
+use assemblage_kv::{
+    storage::{MemoryStorage, Storage},
+    test,
+    timestamp::sleep,
+    BackupPolicy, KvStore, MergePolicy, Result,
+};
+
+#[cfg(target_arch = "wasm32")]
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+const SLOT_0: u8 = 0;
+
+test! {
+    async fn backup_store_to_other_storage(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?;
+        for i in 0..3 {
+            let mut t = store.current().await;
+            t.insert(SLOT_0, "foo", i)?;
+            t.commit().await?;
+        }
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "bar", 10)?;
+        t.remove(SLOT_0, "foo")?;
+        t.commit().await?;
+
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "baz", 20)?;
+
+        let mut backup = MemoryStorage::new();
+        store.backup_to(&mut backup).await?;
+        t.commit().await?;
+        assert!(backup.len() < store.len().await);
+
+        let backup = KvStore::open(backup).await?;
+        let current = backup.current().await;
+        assert_eq!(current.versions(SLOT_0, &"foo").await?.len(), 4);
+        assert_eq!(current.get::<_, u32>(SLOT_0, &"foo").await?, None);
+        assert_eq!(current.get_unremoved(SLOT_0, &"foo").await?, Some(2));
+        assert_eq!(current.get(SLOT_0, &"bar").await?, Some(10));
+        assert_eq!(current.get::<_, u32>(SLOT_0, &"baz").await?, None);
+    }
+}
+
+test! {
+    async fn backup_merged_and_recent_versions(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?;
+        for i in 0..3 {
+            let mut t = store.current().await;
+            t.insert(SLOT_0, "foo", i)?;
+            t.commit().await?;
+        }
+        let last_backup = store.current().await.last_updated().await?.unwrap();
+        sleep(1).await;
+        for i in 0..3 {
+            let mut t = store.current().await;
+            t.insert(SLOT_0, "bar", i)?;
+            t.commit().await?;
+        }
+
+        let policy = BackupPolicy {
+            merge: Some(MergePolicy::default()),
+            ..BackupPolicy::default()
+        };
+        let mut backup = MemoryStorage::new();
+        store.backup_to_with(&mut backup, policy).await?;
+        let backup = KvStore::open(backup).await?;
+        let current = backup.current().await;
+        assert_eq!(current.versions(SLOT_0, &"foo").await?.len(), 1);
+        assert_eq!(current.get(SLOT_0, &"foo").await?, Some(2));
+        assert_eq!(current.get(SLOT_0, &"bar").await?, Some(2));
+
+        let policy = BackupPolicy {
+            keep_versions_newer_than: Some(last_backup),
+            ..BackupPolicy::default()
+        };
+        let mut backup = MemoryStorage::new();
+        store.backup_to_with(&mut backup, policy).await?;
+        let backup = KvStore::open(backup).await?;
+        let current = backup.current().await;
+        assert_eq!(current.versions(SLOT_0, &"bar").await?.len(), 3);
+        assert_eq!(current.get(SLOT_0, &"bar").await?, Some(2));
+        assert_eq!(current.get::<_, u32>(SLOT_0, &"foo").await?, None);
+    }
+}
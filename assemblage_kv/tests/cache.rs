@@ -0,0 +1,60 @@
+use assemblage_kv::{test, KvStore, Result};
+
+#[cfg(target_arch = "wasm32")]
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+const SLOT_0: u8 = 0;
+
+test! {
+    async fn share_cached_values_across_snapshots(storage) -> Result<()> {
+        let mut store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "root", vec![1; 100])?;
+        t.commit().await?;
+
+        for _ in 0..3 {
+            let t = store.current().await;
+            assert_eq!(t.get(SLOT_0, &"root").await?, Some(vec![1; 100]));
+        }
+        let stats = store.stats().await?;
+        assert_eq!((stats.cache_hits, stats.cache_misses), (2, 1));
+
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "root", vec![2; 100])?;
+        t.commit().await?;
+        store.merge().await?;
+        for _ in 0..2 {
+            let t = store.current().await;
+            assert_eq!(t.get(SLOT_0, &"root").await?, Some(vec![2; 100]));
+        }
+        let stats = store.stats().await?;
+        assert_eq!((stats.cache_hits, stats.cache_misses), (3, 2));
+    }
+}
+
+test! {
+    async fn evict_least_recently_used_values(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?.with_cache_capacity(400);
+        let mut t = store.current().await;
+        for k in ["a", "b", "c"].iter() {
+            t.insert(SLOT_0, k, vec![0; 100])?;
+        }
+        t.commit().await?;
+
+        for k in ["a", "b", "a", "c", "a", "b"].iter() {
+            let t = store.current().await;
+            assert_eq!(t.get(SLOT_0, k).await?, Some(vec![0; 100]));
+        }
+        // "b" was evicted when "c" was read, "c" when "b" was read again:
+        let stats = store.stats().await?;
+        assert_eq!((stats.cache_hits, stats.cache_misses), (2, 4));
+
+        let store = store.with_cache_capacity(0);
+        for _ in 0..2 {
+            let t = store.current().await;
+            assert_eq!(t.get(SLOT_0, &"a").await?, Some(vec![0; 100]));
+        }
+        let stats = store.stats().await?;
+        assert_eq!((stats.cache_hits, stats.cache_misses), (2, 6));
+    }
+}
@@ -0,0 +1,52 @@
+use assemblage_kv::{storage, storage::Storage, test, KvStore, MergePolicy, Result};
+
+#[cfg(target_arch = "wasm32")]
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+const SLOT_0: u8 = 0;
+
+test! {
+    async fn insert_and_merge_chunked_values(storage) -> Result<()> {
+        let store_name = String::from(storage.name());
+        let store = KvStore::open(storage).await?;
+        let large: Vec<u32> = (0..5_000_000).collect();
+
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "large", &large)?;
+        t.insert(SLOT_0, "small", 1)?;
+        t.commit().await?;
+        {
+            let current = store.current().await;
+            assert_eq!(current.get::<_, Vec<u32>>(SLOT_0, &"large").await?, Some(large.clone()));
+            assert_eq!(current.keys::<String>(SLOT_0).await?, vec!["large", "small"]);
+        }
+
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "large", vec![1, 2, 3])?;
+        t.commit().await?;
+
+        let storage = storage::open(&store_name).await?;
+        let mut store = KvStore::open(storage).await?;
+        let policy = MergePolicy {
+            keep_last_n: 2,
+            ..MergePolicy::default()
+        };
+        store.merge_with(policy).await?;
+        {
+            let current = store.current().await;
+            let versions = current.versions(SLOT_0, &"large").await?;
+            assert_eq!(versions.len(), 2);
+            assert_eq!(current.get_version(SLOT_0, &"large", versions[0]).await?, Some(large));
+            assert_eq!(current.get(SLOT_0, &"large").await?, Some(vec![1, 2, 3]));
+            assert_eq!(current.get(SLOT_0, &"small").await?, Some(1));
+        }
+
+        store.merge().await?;
+        let storage = storage::open(&store_name).await?;
+        assert!(storage.len() < 1000);
+        let store = KvStore::open(storage).await?;
+        let current = store.current().await;
+        assert_eq!(current.versions(SLOT_0, &"large").await?.len(), 1);
+        assert_eq!(current.get(SLOT_0, &"large").await?, Some(vec![1, 2, 3]));
+    }
+}
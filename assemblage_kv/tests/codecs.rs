@@ -0,0 +1,102 @@
+use assemblage_kv::{
+    codec::{Codec, MessagePack},
+    test, Error, KvStore, Result,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(target_arch = "wasm32")]
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+const SLOT_0: u8 = 0;
+
+/// A codec that serializes everything as MessagePack, but stores it reversed.
+struct Reversed;
+
+impl Codec for Reversed {
+    fn encode<T: Serialize + ?Sized>(&self, t: &T) -> std::result::Result<Vec<u8>, String> {
+        let mut bytes = MessagePack.encode(t)?;
+        bytes.reverse();
+        Ok(bytes)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> std::result::Result<T, String> {
+        let mut bytes = bytes.to_vec();
+        bytes.reverse();
+        MessagePack.decode(&bytes)
+    }
+}
+
+test! {
+    async fn insert_and_get_raw_bytes(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert_raw(SLOT_0, "foo", vec![0xc1])?;
+        t.insert_raw(SLOT_0, "bar", MessagePack.encode("baz").unwrap())?;
+        t.insert(SLOT_0, "qux", 5)?;
+        t.commit().await?;
+
+        let current = store.current().await;
+        assert_eq!(current.get_raw(SLOT_0, &"foo").await?, Some(vec![0xc1]));
+        assert_eq!(current.get(SLOT_0, &"bar").await?, Some("baz".to_string()));
+        assert_eq!(current.get_raw(SLOT_0, &"qux").await?, Some(vec![5]));
+        assert!(matches!(
+            current.get::<_, u32>(SLOT_0, &"foo").await,
+            Err(Error::InvalidValueError { .. })
+        ));
+
+        let mut t = store.current().await;
+        t.remove(SLOT_0, "foo")?;
+        t.commit().await?;
+        assert_eq!(store.current().await.get_raw(SLOT_0, &"foo").await?, None);
+    }
+}
+
+test! {
+    async fn read_and_write_with_custom_codec(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?.with_codec(Reversed);
+        let mut subscription = store.subscribe().await;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", vec![1, 2, 3])?;
+        t.insert(SLOT_0, 1, "bar")?;
+        t.commit().await?;
+
+        let current = store.current().await;
+        assert_eq!(current.get(SLOT_0, &"foo").await?, Some(vec![1, 2, 3]));
+        assert_eq!(current.get(SLOT_0, &1).await?, Some("bar".to_string()));
+        assert_eq!(current.get_raw(SLOT_0, &1).await?, Some(Reversed.encode("bar").unwrap()));
+        let commit = subscription.recv().await.unwrap();
+        let key: u32 = commit.changes[0].deserialize_key_with(store.codec())?;
+        assert_eq!(key, 1);
+        drop(current);
+
+        let store = KvStore::open(store.into_storage()?).await?;
+        assert_eq!(store.current().await.get_raw(SLOT_0, &"foo").await?, None);
+    }
+}
+
+#[cfg(feature = "bincode")]
+test! {
+    async fn read_and_write_with_bincode(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?.with_codec(assemblage_kv::codec::Bincode);
+        let mut t = store.current().await;
+        t.insert(SLOT_0, (1u8, "foo"), vec![1u32, 2, 3])?;
+        t.commit().await?;
+        let current = store.current().await;
+        assert_eq!(current.get(SLOT_0, &(1u8, "foo")).await?, Some(vec![1u32, 2, 3]));
+        assert_eq!(current.keys::<(u8, String)>(SLOT_0).await?, vec![(1, "foo".to_string())]);
+    }
+}
+
+#[cfg(feature = "cbor")]
+test! {
+    async fn read_and_write_with_cbor(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?.with_codec(assemblage_kv::codec::Cbor);
+        let mut t = store.current().await;
+        for i in 0..10u32 {
+            t.insert(SLOT_0, i * 100, i)?;
+        }
+        t.commit().await?;
+        let current = store.current().await;
+        assert_eq!(current.range(SLOT_0, 200..=400u32).await?, vec![(200, 2), (300, 3), (400, 4)]);
+    }
+}
@@ -0,0 +1,34 @@
+#![cfg(feature = "compression")]
+
+use assemblage_kv::{storage, storage::Storage, test, KvStore, Result};
+
+#[cfg(target_arch = "wasm32")]
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+const SLOT_0: u8 = 0;
+
+test! {
+    async fn compress_repetitive_values(storage) -> Result<()> {
+        let store_name = String::from(storage.name());
+        let store = KvStore::open(storage).await?;
+        let repetitive = vec!["node"; 1000];
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "repetitive", &repetitive)?;
+        t.insert(SLOT_0, "short", 1)?;
+        t.commit().await?;
+
+        let storage = storage::open(&store_name).await?;
+        assert!(storage.len() < 1000);
+        let mut store = KvStore::open(storage).await?;
+        {
+            let current = store.current().await;
+            assert_eq!(current.get::<_, Vec<String>>(SLOT_0, &"repetitive").await?.unwrap(), repetitive);
+            assert_eq!(current.get(SLOT_0, &"short").await?, Some(1));
+        }
+
+        store.merge().await?;
+        let current = store.current().await;
+        assert_eq!(current.get::<_, Vec<String>>(SLOT_0, &"repetitive").await?.unwrap(), repetitive);
+        assert_eq!(current.get(SLOT_0, &"short").await?, Some(1));
+    }
+}
@@ -0,0 +1,38 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+use assemblage_kv::{
+    storage::{self, Durability, Storage},
+    test, KvStore, Result,
+};
+
+const SLOT_0: u8 = 0;
+
+test! {
+    async fn commit_with_relaxed_durability(storage) -> Result<()> {
+        let store_name = String::from(storage.name());
+        assert_eq!(storage.durability(), Durability::EveryCommit);
+
+        let storage = storage.with_durability(Durability::Periodic(60 * 1000));
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        for i in 0..1000 {
+            t.insert(SLOT_0, i, i * 10)?;
+        }
+        t.commit().await?;
+        drop(store);
+
+        let storage = storage::open(&store_name).await?.with_durability(Durability::Never);
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        assert_eq!(t.keys::<u32>(SLOT_0).await?.len(), 1000);
+        t.insert(SLOT_0, 0, 1)?;
+        t.commit().await?;
+        drop(store);
+
+        let storage = storage::open(&store_name).await?;
+        let store = KvStore::open(storage).await?;
+        let current = store.current().await;
+        assert_eq!(current.get(SLOT_0, &0).await?, Some(1));
+        assert_eq!(current.get(SLOT_0, &999).await?, Some(9990));
+    }
+}
@@ -0,0 +1,137 @@
+#![cfg(feature = "encryption")]
+
+use assemblage_kv::{hint_name, storage, storage::Storage, test, Error, KvStore, Result};
+
+#[cfg(target_arch = "wasm32")]
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+const SLOT_0: u8 = 0;
+const KEY: [u8; 32] = [7; 32];
+const WRONG_KEY: [u8; 32] = [8; 32];
+
+test! {
+    async fn open_encrypted_store_with_correct_key_only(storage) -> Result<()> {
+        let store_name = String::from(storage.name());
+        let store = KvStore::open_encrypted(storage, &KEY).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "secret key", "secret value")?;
+        t.commit().await?;
+        let len = store.len().await;
+        drop(store);
+
+        assert!(!contains(&store_name, "secret").await?);
+
+        let storage = storage::open(&store_name).await?;
+        let result = KvStore::open_encrypted(storage, &WRONG_KEY).await;
+        assert!(matches!(result, Err(Error::InvalidEncryptionKey)));
+
+        let storage = storage::open(&store_name).await?;
+        let result = KvStore::open(storage).await;
+        assert!(matches!(result, Err(Error::InvalidEncryptionKey)));
+
+        let storage = storage::open(&store_name).await?;
+        assert_eq!(storage.len(), len);
+        let store = KvStore::open_encrypted(storage, &KEY).await?;
+        let current = store.current().await;
+        let value = current.get::<_, String>(SLOT_0, &"secret key").await?;
+        assert_eq!(value.unwrap(), "secret value");
+    }
+}
+
+test! {
+    async fn encrypt_unencrypted_entries_during_merge(storage) -> Result<()> {
+        let store_name = String::from(storage.name());
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "plain key", "plain value")?;
+        t.commit().await?;
+        drop(store);
+
+        let storage = storage::open(&store_name).await?;
+        let mut store = KvStore::open_encrypted(storage, &KEY).await?;
+        let mut t = store.current().await;
+        assert_eq!(t.get::<_, String>(SLOT_0, &"plain key").await?.unwrap(), "plain value");
+        t.insert(SLOT_0, "secret key", "secret value")?;
+        t.commit().await?;
+        assert!(contains(&store_name, "plain").await?);
+        assert!(!contains(&store_name, "secret").await?);
+
+        store.merge().await?;
+        drop(store);
+        assert!(!contains(&store_name, "plain").await?);
+        assert!(!contains(&hint_name(&store_name), "plain").await?);
+
+        let storage = storage::open(&store_name).await?;
+        let result = KvStore::open(storage).await;
+        assert!(matches!(result, Err(Error::InvalidEncryptionKey)));
+
+        let storage = storage::open(&store_name).await?;
+        let store = KvStore::open_encrypted(storage, &KEY).await?;
+        let current = store.current().await;
+        assert_eq!(current.get::<_, String>(SLOT_0, &"plain key").await?.unwrap(), "plain value");
+        assert_eq!(current.get::<_, String>(SLOT_0, &"secret key").await?.unwrap(), "secret value");
+    }
+}
+
+test! {
+    async fn fail_to_decrypt_values_swapped_between_keys(storage) -> Result<()> {
+        let store_name = String::from(storage.name());
+        let store = KvStore::open_encrypted(storage, &KEY).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "key a", "value a")?;
+        t.insert(SLOT_0, "key b", "value b")?;
+        t.commit().await?;
+        drop(store);
+
+        // the transaction consists of 2 kv entries (header, sizes, key, value),
+        // followed by the commit, whose CRC is recomputed after the swap:
+        let mut storage = storage::open(&store_name).await?;
+        let mut bytes = storage.read(0, storage.len() as u32).await?;
+        let mut values = Vec::new();
+        let mut offset = 0;
+        for _ in 0..2 {
+            let header = bytes[offset];
+            let bytes_val_size = (header & 0b111) as usize;
+            let bytes_key_size = ((header >> 3) & 0b11) as usize;
+            let sizes = &bytes[offset + 1..offset + 1 + bytes_key_size + bytes_val_size];
+            let key_size = int_from_bytes(&sizes[..bytes_key_size]);
+            let val_size = int_from_bytes(&sizes[bytes_key_size..]);
+            let offset_val = offset + 1 + sizes.len() + key_size;
+            values.push(offset_val..offset_val + val_size);
+            offset = offset_val + val_size;
+        }
+        assert_eq!(values[0].len(), values[1].len());
+        let (val_a, val_b) = (bytes[values[0].clone()].to_vec(), bytes[values[1].clone()].to_vec());
+        bytes[values[0].clone()].copy_from_slice(&val_b);
+        bytes[values[1].clone()].copy_from_slice(&val_a);
+        let crc_offset = bytes.len() - 4;
+        let crc = crc32fast::hash(&bytes[..crc_offset]);
+        bytes[crc_offset..].copy_from_slice(&crc.to_le_bytes());
+        storage.truncate(0).await?;
+        storage.write(&bytes).await?;
+        storage.flush().await?;
+        drop(storage);
+
+        let storage = storage::open(&store_name).await?;
+        let store = KvStore::open_encrypted(storage, &KEY).await?;
+        let current = store.current().await;
+        let result = current.get::<_, String>(SLOT_0, &"key a").await;
+        assert!(matches!(result, Err(Error::InvalidEncryptionKey)));
+        let result = current.get::<_, String>(SLOT_0, &"key b").await;
+        assert!(matches!(result, Err(Error::InvalidEncryptionKey)));
+    }
+}
+
+fn int_from_bytes(bytes: &[u8]) -> usize {
+    let mut buf = [0; 8];
+    buf[..bytes.len()].copy_from_slice(bytes);
+    u64::from_le_bytes(buf) as usize
+}
+
+async fn contains(name: &str, plaintext: &str) -> Result<bool> {
+    let mut storage = storage::open(name).await?;
+    let bytes = storage.read(0, storage.len() as u32).await?;
+    Ok(bytes
+        .windows(plaintext.len())
+        .any(|w| w == plaintext.as_bytes()))
+}
@@ -0,0 +1,139 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+use assemblage_kv::{
+    storage::{Error, FileOptions, FileStorage, OpenMode, Storage},
+    test, KvStore, Result,
+};
+use std::{fs, path::Path};
+
+const SLOT_0: u8 = 0;
+
+test! {
+    async fn open_in_directory_with_custom_extension() -> Result<()> {
+        let dir = Path::new("file_storage_custom_dir");
+        fs::create_dir_all(dir).unwrap();
+        let options = FileOptions {
+            extension: String::from("db"),
+            ..FileOptions::default()
+        };
+        let storage = FileStorage::open_at(dir, "store", options.clone()).await?;
+        assert_eq!(storage.path(), dir.join("store.db"));
+        let mut store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 1)?;
+        t.commit().await?;
+        store.merge().await?;
+        drop(store);
+        assert!(dir.join("store.db").exists());
+        assert!(dir.join("store.hint.db").exists());
+        assert!(!Path::new("store.aeon").exists());
+
+        let existing = FileOptions {
+            mode: OpenMode::OpenExisting,
+            ..options.clone()
+        };
+        let storage = FileStorage::open_at(dir, "store", existing.clone()).await?;
+        let store = KvStore::open(storage).await?;
+        assert_eq!(store.current().await.get(SLOT_0, &"foo").await?, Some(1));
+
+        FileStorage::purge_at(dir, "store", &options).await?;
+        FileStorage::purge_at(dir, "store.hint", &options).await?;
+        let result = FileStorage::open_at(dir, "store", existing).await;
+        assert!(matches!(result, Err(Error::IoError(_))));
+        fs::remove_dir(dir).unwrap();
+    }
+}
+
+test! {
+    async fn open_with_create_and_read_only_modes() -> Result<()> {
+        let dir = Path::new("file_storage_modes_dir");
+        fs::create_dir_all(dir).unwrap();
+        let create_new = FileOptions {
+            mode: OpenMode::CreateNew,
+            ..FileOptions::default()
+        };
+        let mut storage = FileStorage::open_at(dir, "store", create_new.clone()).await?;
+        storage.write(&[1, 2, 3]).await?;
+        storage.flush().await?;
+        drop(storage);
+        let result = FileStorage::open_at(dir, "store", create_new).await;
+        assert!(matches!(result, Err(Error::IoError(_))));
+
+        let read_only = FileOptions {
+            mode: OpenMode::ReadOnly,
+            ..FileOptions::default()
+        };
+        let mut storage = FileStorage::open_at(dir, "store", read_only).await?;
+        assert_eq!(storage.read(0, 3).await?, vec![1, 2, 3]);
+        assert!(matches!(storage.write(&[4]).await, Err(Error::ReadOnlyError)));
+        assert!(matches!(storage.truncate(0).await, Err(Error::ReadOnlyError)));
+        assert!(matches!(storage.start_merge().await, Err(Error::ReadOnlyError)));
+        assert_eq!(storage.len(), 3);
+
+        FileStorage::purge_at(dir, "store", &FileOptions::default()).await?;
+        fs::remove_dir(dir).unwrap();
+    }
+}
+
+test! {
+    async fn lock_storage_while_open() -> Result<()> {
+        let dir = Path::new("file_storage_lock_dir");
+        fs::create_dir_all(dir).unwrap();
+        let options = FileOptions {
+            lock: true,
+            ..FileOptions::default()
+        };
+        let storage = FileStorage::open_at(dir, "store", options.clone()).await?;
+        let result = FileStorage::open_at(dir, "store", options.clone()).await;
+        assert!(matches!(result, Err(Error::LockError(_))));
+
+        let read_only = FileOptions {
+            mode: OpenMode::ReadOnly,
+            ..options.clone()
+        };
+        FileStorage::open_at(dir, "store", read_only).await?;
+
+        let result = FileStorage::purge_at(dir, "store", &options).await;
+        assert!(matches!(result, Err(Error::LockError(_))));
+        assert!(storage.path().exists());
+        assert!(dir.join("store.aeon.lock").exists());
+
+        drop(storage);
+        FileStorage::open_at(dir, "store", options.clone()).await?;
+
+        FileStorage::purge_at(dir, "store", &options).await?;
+        fs::remove_dir(dir).unwrap();
+    }
+}
+
+test! {
+    async fn open_read_only_without_creating_hints() -> Result<()> {
+        let dir = Path::new("file_storage_read_only_hint_dir");
+        fs::create_dir_all(dir).unwrap();
+        let options = FileOptions::default();
+        let storage = FileStorage::open_at(dir, "store", options.clone()).await?;
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 1)?;
+        t.commit().await?;
+        drop(store);
+        assert!(!dir.join("store.hint.aeon").exists());
+
+        let storage = FileStorage::open_at(dir, "store", options.clone()).await?;
+        let store = KvStore::open_read_only(storage).await?;
+        assert_eq!(store.current().await.get(SLOT_0, &"foo").await?, Some(1));
+        drop(store);
+        assert!(!dir.join("store.hint.aeon").exists());
+
+        fs::write(dir.join("store.hint.aeon"), []).unwrap();
+        let storage = FileStorage::open_at(dir, "store", options.clone()).await?;
+        let store = KvStore::open_read_only(storage).await?;
+        assert_eq!(store.current().await.get(SLOT_0, &"foo").await?, Some(1));
+        drop(store);
+        assert!(dir.join("store.hint.aeon").exists());
+
+        FileStorage::purge_at(dir, "store", &options).await?;
+        FileStorage::purge_at(dir, "store.hint", &options).await?;
+        fs::remove_dir(dir).unwrap();
+    }
+}
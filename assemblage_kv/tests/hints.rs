@@ -0,0 +1,121 @@
+use assemblage_kv::{hint_name, storage, storage::Storage, test, KvStore, Result};
+
+#[cfg(target_arch = "wasm32")]
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+const SLOT_0: u8 = 0;
+
+test! {
+    async fn open_from_hint_after_merge(storage) -> Result<()> {
+        let store_name = String::from(storage.name());
+        let mut store = KvStore::open(storage).await?;
+
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 1)?;
+        t.insert(SLOT_0, "bar", 1)?;
+        t.commit().await?;
+
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 2)?;
+        t.commit().await?;
+
+        store.merge().await?;
+        assert!(!storage::open(hint_name(&store_name)).await?.is_empty());
+
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 3)?;
+        t.remove(SLOT_0, "bar")?;
+        t.insert(SLOT_0, "baz", 3)?;
+        t.commit().await?;
+        let last_updated = store.current().await.last_updated().await?;
+
+        let storage = storage::open(&store_name).await?;
+        let store = KvStore::open(storage).await?;
+
+        let current = store.current().await;
+        assert_eq!(current.last_updated().await?, last_updated);
+        assert_eq!(current.get(SLOT_0, &"foo").await?, Some(3));
+        assert_eq!(current.get::<_, u32>(SLOT_0, &"bar").await?, None);
+        assert_eq!(current.get_unremoved(SLOT_0, &"bar").await?, Some(1));
+        assert_eq!(current.get(SLOT_0, &"baz").await?, Some(3));
+        assert_eq!(current.versions(SLOT_0, &"foo").await?.len(), 2);
+        assert_eq!(current.keys::<String>(SLOT_0).await?, vec!["baz", "foo"]);
+    }
+}
+
+test! {
+    async fn open_from_explicitly_written_hint(storage) -> Result<()> {
+        let store_name = String::from(storage.name());
+        let store = KvStore::open(storage).await?;
+
+        let mut t = store.current().await;
+        t.insert(SLOT_0, 1, "foo")?;
+        t.commit().await?;
+        store.write_hint().await?;
+
+        let storage = storage::open(&store_name).await?;
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        assert_eq!(t.get::<_, String>(SLOT_0, &1).await?.unwrap(), "foo");
+        t.insert(SLOT_0, 1, "bar")?;
+        t.commit().await?;
+
+        let storage = storage::open(&store_name).await?;
+        let store = KvStore::open(storage).await?;
+        let current = store.current().await;
+        assert_eq!(current.get::<_, String>(SLOT_0, &1).await?.unwrap(), "bar");
+        assert_eq!(current.versions(SLOT_0, &1).await?.len(), 2);
+    }
+}
+
+test! {
+    async fn ignore_stale_hint(storage) -> Result<()> {
+        let store_name = String::from(storage.name());
+        let store = KvStore::open(storage).await?;
+
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", "will be purged")?;
+        t.commit().await?;
+        store.write_hint().await?;
+        drop(store);
+
+        storage::purge(&store_name).await?;
+        let storage = storage::open(&store_name).await?;
+        let store = KvStore::open(storage).await?;
+        assert_eq!(store.current().await.get::<_, String>(SLOT_0, &"foo").await?, None);
+
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "bar", "should remain")?;
+        t.commit().await?;
+
+        let storage = storage::open(&store_name).await?;
+        let store = KvStore::open(storage).await?;
+        let current = store.current().await;
+        assert_eq!(current.get::<_, String>(SLOT_0, &"foo").await?, None);
+        assert_eq!(current.get::<_, String>(SLOT_0, &"bar").await?.unwrap(), "should remain");
+    }
+}
+
+test! {
+    async fn ignore_corrupt_hint(storage) -> Result<()> {
+        let store_name = String::from(storage.name());
+        let store = KvStore::open(storage).await?;
+
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", "foo")?;
+        t.commit().await?;
+        store.write_hint().await?;
+
+        let mut hint = storage::open(hint_name(&store_name)).await?;
+        let len = hint.len();
+        let last_byte = hint.read(len - 1, 1).await?[0];
+        hint.truncate(len - 1).await?;
+        hint.write(&[!last_byte]).await?;
+        hint.flush().await?;
+
+        let storage = storage::open(&store_name).await?;
+        let store = KvStore::open(storage).await?;
+        let current = store.current().await;
+        assert_eq!(current.get::<_, String>(SLOT_0, &"foo").await?.unwrap(), "foo");
+    }
+}
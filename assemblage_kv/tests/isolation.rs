@@ -0,0 +1,148 @@
+use assemblage_kv::{test, Error, Isolation, KvStore, Result};
+
+#[cfg(target_arch = "wasm32")]
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+const SLOT_0: u8 = 0;
+const SLOT_1: u8 = 1;
+
+test! {
+    async fn ignore_keys_whose_values_were_not_read(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 0)?;
+        t.insert(SLOT_1, "bar", 0)?;
+        t.commit().await?;
+
+        let mut t1 = store.current().await;
+        assert_eq!(t1.versions(SLOT_0, &"foo").await?.len(), 1);
+        assert_eq!(t1.get(SLOT_1, &"bar").await?, Some(0));
+
+        let mut t2 = store.current().await;
+        t2.insert(SLOT_0, "foo", 1)?;
+        t2.commit().await?;
+
+        t1.insert(SLOT_1, "bar", 1)?;
+        t1.commit().await?;
+
+        let current = store.current().await;
+        assert_eq!(current.get(SLOT_0, &"foo").await?, Some(1));
+        assert_eq!(current.get(SLOT_1, &"bar").await?, Some(1));
+    }
+}
+
+test! {
+    async fn ignore_values_overwritten_with_identical_values(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", vec![1, 2, 3])?;
+        t.commit().await?;
+
+        let mut t1 = store.current().await;
+        assert_eq!(t1.get(SLOT_0, &"foo").await?, Some(vec![1, 2, 3]));
+        assert_eq!(t1.get::<_, u32>(SLOT_0, &"missing").await?, None);
+
+        let mut t2 = store.current().await;
+        t2.insert(SLOT_0, "foo", vec![1, 2, 3])?;
+        t2.insert(SLOT_0, "missing", 1)?;
+        t2.remove(SLOT_0, "missing")?;
+        t2.commit().await?;
+
+        t1.insert(SLOT_0, "bar", 1)?;
+        t1.commit().await?;
+
+        let mut t1 = store.current().await;
+        assert_eq!(t1.get(SLOT_0, &"foo").await?, Some(vec![1, 2, 3]));
+
+        let mut t2 = store.current().await;
+        t2.insert(SLOT_0, "foo", vec![4, 5, 6])?;
+        t2.commit().await?;
+
+        t1.insert(SLOT_0, "bar", 2)?;
+        assert!(matches!(t1.commit().await, Err(Error::TransactionConflict)));
+    }
+}
+
+test! {
+    async fn detect_only_write_write_conflicts_in_snapshot_isolation(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 0)?;
+        t.insert(SLOT_0, "bar", 0)?;
+        t.commit().await?;
+
+        let mut t1 = store.current_with(Isolation::Snapshot).await;
+        let foo = t1.get::<_, u32>(SLOT_0, &"foo").await?.unwrap();
+
+        let mut t2 = store.current().await;
+        t2.insert(SLOT_0, "foo", 10)?;
+        t2.commit().await?;
+
+        t1.insert(SLOT_0, "bar", foo + 1)?;
+        t1.commit().await?;
+
+        let mut t1 = store.current_with(Isolation::Snapshot).await;
+        let mut t2 = store.current_with(Isolation::Snapshot).await;
+        t2.insert(SLOT_0, "bar", 20)?;
+        t2.commit().await?;
+
+        t1.insert(SLOT_0, "bar", 30)?;
+        assert!(matches!(t1.commit().await, Err(Error::TransactionConflict)));
+
+        let current = store.current().await;
+        assert_eq!(current.get(SLOT_0, &"foo").await?, Some(10));
+        assert_eq!(current.get(SLOT_0, &"bar").await?, Some(20));
+    }
+}
+
+test! {
+    async fn detect_keys_created_or_removed_in_scanned_ranges(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, 1u32, 10u32)?;
+        t.insert(SLOT_0, 2u32, 20u32)?;
+        t.commit().await?;
+
+        let mut t1 = store.current().await;
+        assert_eq!(t1.range::<u32, u32, _>(SLOT_0, 1..3).await?.len(), 2);
+
+        let mut t2 = store.current().await;
+        t2.insert(SLOT_0, 3u32, 30u32)?;
+        t2.insert(SLOT_1, 1u32, 10u32)?;
+        t2.commit().await?;
+
+        // keys outside of the scanned range do not conflict:
+        t1.insert(SLOT_1, "sum", 30)?;
+        t1.commit().await?;
+
+        let mut t1 = store.current().await;
+        assert_eq!(t1.range::<u32, u32, _>(SLOT_0, 1..).await?.len(), 3);
+
+        let mut t2 = store.current().await;
+        t2.insert(SLOT_0, 4u32, 40u32)?;
+        t2.commit().await?;
+
+        t1.insert(SLOT_1, "sum", 60)?;
+        assert!(matches!(t1.commit().await, Err(Error::TransactionConflict)));
+
+        let mut t1 = store.current().await;
+        assert_eq!(t1.keys::<u32>(SLOT_0).await?, vec![1, 2, 3, 4]);
+
+        let mut t2 = store.current().await;
+        t2.remove(SLOT_0, 1u32)?;
+        t2.commit().await?;
+
+        t1.insert(SLOT_1, "count", 4)?;
+        assert!(matches!(t1.commit().await, Err(Error::TransactionConflict)));
+
+        let mut t1 = store.current_with(Isolation::Snapshot).await;
+        assert_eq!(t1.keys::<u32>(SLOT_0).await?, vec![2, 3, 4]);
+
+        let mut t2 = store.current().await;
+        t2.insert(SLOT_0, 5u32, 50u32)?;
+        t2.commit().await?;
+
+        t1.insert(SLOT_1, "count", 3)?;
+        t1.commit().await?;
+    }
+}
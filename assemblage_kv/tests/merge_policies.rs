@@ -0,0 +1,195 @@
+use assemblage_kv::{storage, storage::Storage, test, Error, KvStore, MergePolicy, Result};
+
+#[cfg(target_arch = "wasm32")]
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+const SLOT_0: u8 = 0;
+
+test! {
+    async fn merge_and_keep_last_n_versions(storage) -> Result<()> {
+        let store_name = String::from(storage.name());
+        let mut store = KvStore::open(storage).await?;
+        for i in 0..3 {
+            let mut t = store.current().await;
+            t.insert(SLOT_0, "foo", i)?;
+            t.insert(SLOT_0, "bar", i * 10)?;
+            t.commit().await?;
+        }
+
+        let policy = MergePolicy {
+            keep_last_n: 2,
+            ..MergePolicy::default()
+        };
+        store.merge_with(policy).await?;
+
+        {
+            let current = store.current().await;
+            let versions = current.versions(SLOT_0, &"foo").await?;
+            assert_eq!(versions.len(), 2);
+            assert_eq!(current.get_version(SLOT_0, &"foo", versions[0]).await?, Some(1));
+            assert_eq!(current.get(SLOT_0, &"foo").await?, Some(2));
+            assert_eq!(current.get(SLOT_0, &"bar").await?, Some(20));
+        }
+
+        let storage = storage::open(&store_name).await?;
+        let mut store = KvStore::open(storage).await?;
+        {
+            let current = store.current().await;
+            let versions = current.versions(SLOT_0, &"bar").await?;
+            assert_eq!(versions.len(), 2);
+            assert_eq!(current.get_version(SLOT_0, &"bar", versions[0]).await?, Some(10));
+            assert_eq!(current.get(SLOT_0, &"bar").await?, Some(20));
+        }
+
+        store.merge().await?;
+        let current = store.current().await;
+        assert_eq!(current.versions(SLOT_0, &"foo").await?.len(), 1);
+        assert_eq!(current.get(SLOT_0, &"foo").await?, Some(2));
+    }
+}
+
+test! {
+    async fn merge_and_keep_versions_newer_than_cutoff(storage) -> Result<()> {
+        let store_name = String::from(storage.name());
+        let mut store = KvStore::open(storage).await?;
+        let mut timestamps = Vec::new();
+        for i in 0..3 {
+            let mut t = store.current().await;
+            t.insert(SLOT_0, "foo", i)?;
+            t.commit().await?;
+            timestamps.push(store.current().await.last_updated().await?.unwrap());
+            sleep(10).await;
+        }
+
+        let policy = MergePolicy {
+            keep_versions_newer_than: Some(timestamps[0]),
+            ..MergePolicy::default()
+        };
+        store.merge_with(policy).await?;
+
+        let storage = storage::open(&store_name).await?;
+        let store = KvStore::open(storage).await?;
+        let current = store.current().await;
+        let versions = current.versions(SLOT_0, &"foo").await?;
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].timestamp, timestamps[1]);
+        assert_eq!(versions[1].timestamp, timestamps[2]);
+        assert_eq!(current.get_version(SLOT_0, &"foo", versions[0]).await?, Some(1));
+        assert_eq!(current.get(SLOT_0, &"foo").await?, Some(2));
+    }
+}
+
+test! {
+    async fn merge_and_keep_trash_for_grace_period(storage) -> Result<()> {
+        let store_name = String::from(storage.name());
+        let mut store = KvStore::open(storage).await?;
+
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 1)?;
+        t.insert(SLOT_0, "foo", 2)?;
+        t.insert(SLOT_0, "bar", 1)?;
+        t.commit().await?;
+
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 3)?;
+        t.commit().await?;
+
+        let mut t = store.current().await;
+        t.remove(SLOT_0, "foo")?;
+        t.commit().await?;
+
+        let policy = MergePolicy {
+            keep_trash_for: Some(60 * 60 * 1000),
+            ..MergePolicy::default()
+        };
+        store.merge_with(policy).await?;
+
+        let storage = storage::open(&store_name).await?;
+        let mut store = KvStore::open(storage).await?;
+        {
+            let current = store.current().await;
+            assert_eq!(current.versions(SLOT_0, &"foo").await?.len(), 2);
+            assert_eq!(current.get::<_, u32>(SLOT_0, &"foo").await?, None);
+            assert_eq!(current.get_unremoved(SLOT_0, &"foo").await?, Some(3));
+            assert_eq!(current.get(SLOT_0, &"bar").await?, Some(1));
+        }
+
+        // keeping the trash forever must not overflow:
+        let policy = MergePolicy {
+            keep_trash_for: Some(u64::MAX),
+            ..MergePolicy::default()
+        };
+        store.merge_with(policy).await?;
+        assert_eq!(store.current().await.versions(SLOT_0, &"foo").await?.len(), 2);
+
+        let policy = MergePolicy {
+            keep_trash_for: Some(0),
+            ..MergePolicy::default()
+        };
+        store.merge_with(policy).await?;
+        let current = store.current().await;
+        assert_eq!(current.versions(SLOT_0, &"foo").await?.len(), 1);
+        assert_eq!(current.get_unremoved::<_, u32>(SLOT_0, &"foo").await?, None);
+        assert_eq!(current.get(SLOT_0, &"bar").await?, Some(1));
+    }
+}
+
+test! {
+    async fn merge_incrementally_while_committing(storage) -> Result<()> {
+        let store_name = String::from(storage.name());
+        let store = KvStore::open(storage).await?;
+        for i in 0..20 {
+            let mut t = store.current().await;
+            t.insert(SLOT_0, i, i)?;
+            t.insert(SLOT_0, i, i * 10)?;
+            t.commit().await?;
+        }
+        let snapshot_before_merge = store.current().await;
+
+        let merge = store.merge_incrementally(MergePolicy::default(), 64);
+        let commits = async {
+            for i in 20..30 {
+                loop {
+                    let mut t = store.current().await;
+                    t.insert(SLOT_0, i, i * 10)?;
+                    match t.commit().await {
+                        Err(Error::TransactionConflict) => continue,
+                        result => break result?,
+                    }
+                }
+            }
+            Result::Ok(())
+        };
+        let (merged, committed) = futures::join!(merge, commits);
+        merged?;
+        committed?;
+
+        let mut t = snapshot_before_merge;
+        t.insert(SLOT_0, 0, 0)?;
+        assert!(matches!(t.commit().await, Err(Error::TransactionConflict)));
+
+        let storage = storage::open(&store_name).await?;
+        let store = KvStore::open(storage).await?;
+        let current = store.current().await;
+        for i in 0..30 {
+            assert_eq!(current.versions(SLOT_0, &i).await?.len(), 1);
+            assert_eq!(current.get(SLOT_0, &i).await?, Some(i * 10));
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn sleep(millis: u64) {
+    let promise = js_sys::Promise::new(&mut |yes, _| {
+        let win = web_sys::window().unwrap();
+        win.set_timeout_with_callback_and_timeout_and_arguments_0(&yes, millis as i32)
+            .unwrap();
+    });
+    let js_fut = wasm_bindgen_futures::JsFuture::from(promise);
+    js_fut.await.unwrap();
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn sleep(millis: u64) {
+    tokio::time::sleep(std::time::Duration::from_millis(millis)).await;
+}
@@ -0,0 +1,73 @@
+#![cfg(all(feature = "mmap", not(target_arch = "wasm32")))]
+
+use assemblage_kv::{storage, storage::Storage, test, KvStore, Result};
+
+const SLOT_0: u8 = 0;
+
+test! {
+    async fn read_concurrently_from_mapped_file(storage) -> Result<()> {
+        let store_name = String::from(storage.name());
+        let mut store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        for i in 0..100 {
+            t.insert(SLOT_0, i, i * 10)?;
+        }
+        t.commit().await?;
+
+        let snapshot = store.current().await;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, 0, 1)?;
+        t.commit().await?;
+
+        let (store_ref, snapshot_ref) = (&store, &snapshot);
+        let reads = (0..100).map(|i| async move {
+            let current = store_ref.current().await;
+            let (before, after) = futures::join!(snapshot_ref.get(SLOT_0, &i), current.get(SLOT_0, &i));
+            Result::Ok((before?, after?))
+        });
+        for (i, read) in futures::future::join_all(reads).await.into_iter().enumerate() {
+            let (before, after): (Option<u32>, Option<u32>) = read?;
+            assert_eq!(before, Some(i as u32 * 10));
+            assert_eq!(after, Some(if i == 0 { 1 } else { i as u32 * 10 }));
+        }
+        drop(snapshot);
+
+        store.merge().await?;
+        let mut t = store.current().await;
+        assert_eq!(t.get(SLOT_0, &0).await?, Some(1));
+        t.insert(SLOT_0, 100, 1000)?;
+        t.commit().await?;
+
+        let storage = storage::open(&store_name).await?;
+        let store = KvStore::open(storage).await?;
+        let current = store.current().await;
+        assert_eq!(current.get(SLOT_0, &99).await?, Some(990));
+        assert_eq!(current.get(SLOT_0, &100).await?, Some(1000));
+    }
+}
+
+test! {
+    async fn refuse_to_truncate_mapped_bytes(storage) -> Result<()> {
+        let mut storage = storage;
+        storage.write(&[1; 100]).await?;
+        let view = storage.view().expect("file storages support views");
+        assert_eq!(view.len(), 100);
+
+        storage.write(&[2; 20]).await?;
+        assert_eq!(storage.read(90, 20).await?, [&[1; 10][..], &[2; 10][..]].concat());
+        assert_eq!(storage.view().map(|view| view.len()), Some(100));
+        storage.truncate(110).await?;
+        match storage.truncate(50).await {
+            Err(storage::Error::ViewInUse {
+                offset: 50,
+                view_length: 100,
+            }) => {}
+            result => panic!("expected the view to be in use, found {:?}", result),
+        }
+
+        drop(view);
+        storage.truncate(50).await?;
+        assert_eq!(storage.len(), 50);
+        assert_eq!(storage.read(40, 20).await?, [&[1; 10][..], &[0; 10][..]].concat());
+    }
+}
@@ -0,0 +1,279 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+use assemblage_kv::{
+    hint_name,
+    storage::{self, FileStorage, SegmentOptions, SegmentedStorage, Storage},
+    test, CompactionPolicy, Error, KvStore, RepairStrategy, Result, Snapshot,
+};
+use async_trait::async_trait;
+use std::{cell::Cell, fs, io, path::Path, rc::Rc};
+
+const SLOT_0: u8 = 0;
+
+type FailWrites = Rc<Cell<Option<fn(&str, usize) -> bool>>>;
+
+/// A file storage that fails all writes whose storage name and length match the
+/// predicate (which is shared with all of its siblings).
+struct FailingStorage {
+    file: FileStorage,
+    fail_writes: FailWrites,
+}
+
+impl FailingStorage {
+    async fn open(name: &str) -> Result<(Self, FailWrites)> {
+        let storage = <Self as Storage>::open(name).await?;
+        let fail_writes = storage.fail_writes.clone();
+        Ok((storage, fail_writes))
+    }
+}
+
+#[async_trait(?Send)]
+impl Storage for FailingStorage {
+    async fn open<'a>(name: impl Into<String> + 'a) -> storage::Result<Self> {
+        Ok(Self {
+            file: FileStorage::open(name).await?,
+            fail_writes: Rc::new(Cell::new(None)),
+        })
+    }
+
+    async fn purge<'a>(name: impl Into<String> + 'a) -> storage::Result<()> {
+        FileStorage::purge(name).await
+    }
+
+    async fn open_sibling(&self, name: &str) -> storage::Result<Self> {
+        Ok(Self {
+            file: self.file.open_sibling(name).await?,
+            fail_writes: self.fail_writes.clone(),
+        })
+    }
+
+    fn name(&self) -> &str {
+        self.file.name()
+    }
+
+    fn len(&self) -> u64 {
+        self.file.len()
+    }
+
+    async fn read(&mut self, offset: u64, bytes: u32) -> storage::Result<Vec<u8>> {
+        self.file.read(offset, bytes).await
+    }
+
+    async fn write(&mut self, buffer: &[u8]) -> storage::Result<u64> {
+        match self.fail_writes.get() {
+            Some(fail) if fail(self.file.name(), buffer.len()) => {
+                let e = io::Error::other("simulated crash");
+                Err(storage::Error::IoError(e))
+            }
+            _ => self.file.write(buffer).await,
+        }
+    }
+
+    async fn truncate(&mut self, offset: u64) -> storage::Result<()> {
+        self.file.truncate(offset).await
+    }
+
+    async fn flush(&mut self) -> storage::Result<()> {
+        self.file.flush().await
+    }
+
+    async fn start_merge(&mut self) -> storage::Result<()> {
+        self.file.start_merge().await
+    }
+
+    async fn stop_merge(&mut self) -> storage::Result<()> {
+        self.file.stop_merge().await
+    }
+
+    async fn pause_merge(&mut self) -> storage::Result<()> {
+        self.file.pause_merge().await
+    }
+
+    async fn resume_merge(&mut self) -> storage::Result<()> {
+        self.file.resume_merge().await
+    }
+
+    async fn abort_merge(&mut self) -> storage::Result<()> {
+        self.file.abort_merge().await
+    }
+}
+
+async fn purge(name: &str) -> Result<()> {
+    storage::purge(name).await?;
+    storage::purge(hint_name(name)).await?;
+    Ok(())
+}
+
+fn decisions(name: &str) -> usize {
+    fs::read_dir(".")
+        .unwrap()
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|file| file.starts_with(name) && file.ends_with(".decision.aeon"))
+        .count()
+}
+
+test! {
+    async fn finish_interrupted_multi_store_commit() -> Result<()> {
+        let (a, b) = ("multi_store_finish_a", "multi_store_finish_b");
+        purge(a).await?;
+        purge(b).await?;
+        let store_a = KvStore::open(FailingStorage::open(a).await?.0).await?;
+        let (storage_b, fail_writes_b) = FailingStorage::open(b).await?;
+        let store_b = KvStore::open(storage_b).await?;
+
+        let mut t_a = store_a.current().await;
+        t_a.insert(SLOT_0, "foo", 1)?;
+        let mut t_b = store_b.current().await;
+        t_b.insert(SLOT_0, "bar", 2)?;
+        // the process "crashes" after the commit of a, before the commit of b:
+        fail_writes_b.set(Some(|name, _| name == "multi_store_finish_b"));
+        let result = Snapshot::commit_all(vec![t_b, t_a]).await;
+        assert!(matches!(result, Err(Error::StorageError(_))));
+        assert_eq!(store_a.current().await.get(SLOT_0, &"foo").await?, Some(1));
+        assert_eq!(decisions(a), 1);
+
+        // b is out of sync with its storage until it is reopened:
+        fail_writes_b.set(None);
+        let mut t_b = store_b.current().await;
+        let result = t_b.keys::<String>(SLOT_0).await;
+        assert!(matches!(result, Err(Error::InconsistentStore)));
+        let result = t_b.insert(SLOT_0, "bar", 3);
+        assert!(matches!(result, Err(Error::InconsistentStore)));
+        drop(t_b);
+        drop(store_b);
+
+        // repairing the store recovers the interrupted transaction first:
+        let mut storage_b = FailingStorage::open(b).await?.0;
+        assert!(KvStore::repair(&mut storage_b, RepairStrategy::Truncate).await?.is_ok());
+        assert!(!Path::new(&format!("{}.intent.aeon", b)).exists());
+        assert_eq!(decisions(a), 0);
+        let store_b = KvStore::open(storage_b).await?;
+        assert_eq!(store_b.current().await.get(SLOT_0, &"bar").await?, Some(2));
+
+        let mut t_a = store_a.current().await;
+        t_a.insert(SLOT_0, "foo", 3)?;
+        let mut t_b = store_b.current().await;
+        t_b.insert(SLOT_0, "bar", 4)?;
+        Snapshot::commit_all(vec![t_a, t_b]).await?;
+        drop(store_a);
+        drop(store_b);
+        let store_a = KvStore::open(FailingStorage::open(a).await?.0).await?;
+        let store_b = KvStore::open(FailingStorage::open(b).await?.0).await?;
+        assert_eq!(store_a.current().await.get(SLOT_0, &"foo").await?, Some(3));
+        assert_eq!(store_b.current().await.get(SLOT_0, &"bar").await?, Some(4));
+
+        purge(a).await?;
+        purge(b).await?;
+    }
+}
+
+test! {
+    async fn roll_back_failed_multi_store_commit() -> Result<()> {
+        let (a, b) = ("multi_store_rollback_a", "multi_store_rollback_b");
+        purge(a).await?;
+        purge(b).await?;
+        let store_a = KvStore::open(FailingStorage::open(a).await?.0).await?;
+        let (storage_b, fail_writes_b) = FailingStorage::open(b).await?;
+        let store_b = KvStore::open(storage_b).await?;
+
+        let mut t_a = store_a.current().await;
+        t_a.insert(SLOT_0, "foo", 1)?;
+        let mut t_b = store_b.current().await;
+        t_b.insert(SLOT_0, "bar", 2)?;
+        // the intent of b cannot be stored, so neither store is modified:
+        fail_writes_b.set(Some(|name, _| name.ends_with(".intent")));
+        let result = Snapshot::commit_all(vec![t_a, t_b]).await;
+        assert!(matches!(result, Err(Error::StorageError(_))));
+        fail_writes_b.set(None);
+        assert!(!Path::new(&format!("{}.intent.aeon", a)).exists());
+        assert!(!Path::new(&format!("{}.intent.aeon", b)).exists());
+        assert!(store_a.is_empty().await);
+        assert!(store_b.is_empty().await);
+        assert_eq!(store_a.current().await.get::<_, u32>(SLOT_0, &"foo").await?, None);
+        assert_eq!(decisions(a), 0);
+
+        let mut t_a = store_a.current().await;
+        t_a.insert(SLOT_0, "foo", 3)?;
+        let mut t_a2 = store_a.current().await;
+        t_a2.insert(SLOT_0, "baz", 3)?;
+        let result = Snapshot::commit_all(vec![t_a, t_a2]).await;
+        assert!(matches!(result, Err(Error::DuplicateStore { .. })));
+
+        let mut t_a = store_a.current().await;
+        t_a.get::<_, u32>(SLOT_0, &"foo").await?;
+        t_a.insert(SLOT_0, "foo", 4)?;
+        let mut t_b = store_b.current().await;
+        t_b.insert(SLOT_0, "bar", 5)?;
+        let mut t_conflict = store_a.current().await;
+        t_conflict.insert(SLOT_0, "foo", 6)?;
+        t_conflict.commit().await?;
+        let result = Snapshot::commit_all(vec![t_a, t_b]).await;
+        assert!(matches!(result, Err(Error::TransactionConflict)));
+        assert_eq!(store_b.current().await.get::<_, u32>(SLOT_0, &"bar").await?, None);
+        drop(store_a);
+        let store_a = KvStore::open(FailingStorage::open(a).await?.0).await?;
+        assert_eq!(store_a.current().await.get(SLOT_0, &"foo").await?, Some(6));
+
+        purge(a).await?;
+        purge(b).await?;
+    }
+}
+
+test! {
+    async fn commit_multi_store_transactions_to_segments() -> Result<()> {
+        let dir = Path::new("multi_store_segmented_dir");
+        fs::create_dir_all(dir).unwrap();
+        let options = SegmentOptions {
+            segment_size: 100,
+            ..SegmentOptions::default()
+        };
+        let storage_a = SegmentedStorage::open_at(dir, "a", options.clone()).await?;
+        let storage_b = SegmentedStorage::open_at(dir, "b", options.clone()).await?;
+        let mut store_a = KvStore::open(storage_a).await?;
+        let mut store_b = KvStore::open(storage_b).await?;
+        for i in 0..10u8 {
+            let mut t_a = store_a.current().await;
+            t_a.insert(SLOT_0, "foo", vec![i; 40])?;
+            t_a.insert(SLOT_0, format!("a{}", i), i)?;
+            let mut t_b = store_b.current().await;
+            t_b.insert(SLOT_0, "bar", vec![i; 40])?;
+            t_b.insert(SLOT_0, format!("b{}", i), i)?;
+            Snapshot::commit_all(vec![t_a, t_b]).await?;
+        }
+
+        let policy = CompactionPolicy {
+            min_garbage_ratio: 0.0,
+            ..CompactionPolicy::default()
+        };
+        store_a.compact_with(policy).await?;
+        store_b.compact_with(policy).await?;
+        assert_eq!(store_a.current().await.get(SLOT_0, &"foo").await?, Some(vec![9u8; 40]));
+        assert_eq!(store_b.current().await.get(SLOT_0, &"bar").await?, Some(vec![9u8; 40]));
+        let storage_a = store_a.into_storage()?;
+        let storage_b = store_b.into_storage()?;
+        assert!(storage_a.segment_ranges().len() > 2);
+        assert!(storage_b.segment_ranges().len() > 2);
+        storage_a.purge_sibling(&hint_name("a")).await?;
+        storage_b.purge_sibling(&hint_name("b")).await?;
+
+        let store_a = KvStore::open(storage_a).await?;
+        let store_b = KvStore::open(storage_b).await?;
+        assert_eq!(store_a.current().await.get(SLOT_0, &"foo").await?, Some(vec![9u8; 40]));
+        assert_eq!(store_b.current().await.get(SLOT_0, &"bar").await?, Some(vec![9u8; 40]));
+        // every transaction is contained in a single segment, so compacting a
+        // segment never invalidates the commit of a transaction:
+        for i in 0..10u8 {
+            let a = store_a.current().await.get(SLOT_0, &format!("a{}", i)).await?;
+            let b = store_b.current().await.get(SLOT_0, &format!("b{}", i)).await?;
+            assert_eq!((a, b), (Some(i), Some(i)));
+        }
+        drop(store_a);
+        drop(store_b);
+
+        for name in ["a", "b"] {
+            SegmentedStorage::purge_at(dir, name).await?;
+            SegmentedStorage::purge_at(dir, hint_name(name)).await?;
+        }
+        fs::remove_dir(dir).unwrap();
+    }
+}
@@ -0,0 +1,103 @@
+use assemblage_kv::{storage::MemoryStorage, test, Error, KvStore, Result};
+
+#[cfg(target_arch = "wasm32")]
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+const SLOT_0: u8 = 0;
+
+test! {
+    async fn ship_transactions_to_replica(storage) -> Result<()> {
+        let mut primary = KvStore::open(storage).await?;
+        let replica = KvStore::open(MemoryStorage::new()).await?;
+        for i in 0..3 {
+            let mut t = primary.current().await;
+            t.insert(SLOT_0, "foo", i)?;
+            t.insert(SLOT_0, i, vec![i; 100])?;
+            t.commit().await?;
+        }
+
+        let frames = primary.transactions_since(replica.len().await, u64::MAX).await?;
+        assert_eq!(frames.len(), 3);
+        for frame in frames.iter() {
+            replica.apply(frame).await?;
+        }
+        assert_eq!(replica.len().await, primary.len().await);
+        assert_eq!(replica.current().await.get(SLOT_0, &"foo").await?, Some(2));
+        assert_eq!(replica.current().await.versions(SLOT_0, &"foo").await?.len(), 3);
+        assert!(matches!(
+            replica.apply(&frames[2]).await,
+            Err(Error::InvalidFrameError { .. })
+        ));
+
+        let mut subscription = replica.subscribe().await;
+        let mut t = primary.current().await;
+        t.remove(SLOT_0, "foo")?;
+        t.commit().await?;
+        let frames = primary.transactions_since(replica.len().await, u64::MAX).await?;
+        assert_eq!(frames.len(), 1);
+        let mut tampered = frames[0].clone();
+        tampered.bytes[2] = !tampered.bytes[2];
+        assert!(matches!(
+            replica.apply(&tampered).await,
+            Err(Error::CorruptDataError(_))
+        ));
+        replica.apply(&frames[0]).await?;
+        let commit = subscription.recv().await.unwrap();
+        assert_eq!(commit.changes.len(), 1);
+        assert!(commit.changes[0].is_removed);
+        assert_eq!(replica.current().await.get::<_, u32>(SLOT_0, &"foo").await?, None);
+        assert_eq!(replica.current().await.get(SLOT_0, &1).await?, Some(vec![1; 100]));
+
+        primary.merge().await?;
+        let mut t = primary.current().await;
+        t.insert(SLOT_0, "foo", 3)?;
+        t.commit().await?;
+        let shipped = match primary.transactions_since(replica.len().await, u64::MAX).await {
+            Ok(frames) => replica.apply(&frames[0]).await,
+            Err(e) => Err(e),
+        };
+        assert!(matches!(shipped, Err(Error::InvalidFrameError { .. })));
+    }
+}
+
+test! {
+    async fn ship_transactions_in_batches(storage) -> Result<()> {
+        let primary = KvStore::open(storage).await?;
+        let replica = KvStore::open(MemoryStorage::new()).await?;
+        for i in 0..5 {
+            let mut t = primary.current().await;
+            t.insert(SLOT_0, i, vec![i; 100])?;
+            t.commit().await?;
+        }
+
+        let all_frames = primary.transactions_since(0, u64::MAX).await?;
+        assert_eq!(all_frames.len(), 5);
+        // all transactions have the same size, so that exactly 2 fit in a batch:
+        let max_bytes = all_frames[1].end();
+        let mut batches = Vec::new();
+        loop {
+            let frames = primary
+                .transactions_since(replica.len().await, max_bytes)
+                .await?;
+            if frames.is_empty() {
+                break;
+            }
+            for frame in frames.iter() {
+                replica.apply(frame).await?;
+            }
+            batches.push(frames.len());
+        }
+        assert_eq!(batches, vec![2, 2, 1]);
+        assert_eq!(replica.len().await, primary.len().await);
+        assert_eq!(replica.current().await.get(SLOT_0, &4).await?, Some(vec![4; 100]));
+
+        let frames = primary.transactions_since(0, 0).await?;
+        assert_eq!(frames, all_frames[..1]);
+
+        let read_only = KvStore::open_read_only(MemoryStorage::new()).await?;
+        assert!(matches!(
+            read_only.apply(&frames[0]).await,
+            Err(Error::ReadOnlyStore)
+        ));
+    }
+}
@@ -0,0 +1,98 @@
+use assemblage_kv::{test, Error, KvStore, Result};
+
+#[cfg(target_arch = "wasm32")]
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+const SLOT_0: u8 = 0;
+
+test! {
+    async fn roll_back_to_nested_savepoints(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 1)?;
+        let outer = t.savepoint();
+        t.insert(SLOT_0, "foo", 2)?;
+        t.insert(SLOT_0, "bar", 2)?;
+        let inner = t.savepoint();
+        t.remove(SLOT_0, "foo")?;
+        t.insert(SLOT_0, "baz", 3)?;
+        assert_eq!(t.get::<_, u32>(SLOT_0, &"foo").await?, None);
+
+        t.rollback_to(inner)?;
+        assert_eq!(t.get(SLOT_0, &"foo").await?, Some(2));
+        assert_eq!(t.get::<_, u32>(SLOT_0, &"baz").await?, None);
+
+        let inner = t.savepoint();
+        t.insert(SLOT_0, "foo", 4)?;
+        t.rollback_to(outer)?;
+        assert!(matches!(t.release(inner), Err(Error::UnknownSavepoint)));
+        assert_eq!(t.get(SLOT_0, &"foo").await?, Some(1));
+        assert_eq!(t.get::<_, u32>(SLOT_0, &"bar").await?, None);
+        t.commit().await?;
+
+        let t = store.current().await;
+        assert_eq!(t.get(SLOT_0, &"foo").await?, Some(1));
+        assert_eq!(t.keys::<String>(SLOT_0).await?, vec!["foo".to_string()]);
+    }
+}
+
+test! {
+    async fn release_savepoints_into_enclosing_savepoint(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 1)?;
+        let outer = t.savepoint();
+        t.insert(SLOT_0, "bar", 2)?;
+        let inner = t.savepoint();
+        t.insert(SLOT_0, "foo", 3)?;
+        t.release(inner)?;
+        assert_eq!(t.get(SLOT_0, &"foo").await?, Some(3));
+
+        let kept = t.savepoint();
+        t.insert(SLOT_0, "baz", 4)?;
+        t.release(kept)?;
+        t.rollback_to(outer)?;
+        assert_eq!(t.get(SLOT_0, &"foo").await?, Some(1));
+        assert_eq!(t.get::<_, u32>(SLOT_0, &"bar").await?, None);
+        assert_eq!(t.get::<_, u32>(SLOT_0, &"baz").await?, None);
+
+        let sp = t.savepoint();
+        t.insert(SLOT_0, "bar", 5)?;
+        t.release(sp)?;
+        t.commit().await?;
+
+        let t = store.current().await;
+        assert_eq!(t.get(SLOT_0, &"foo").await?, Some(1));
+        assert_eq!(t.get(SLOT_0, &"bar").await?, Some(5));
+    }
+}
+
+test! {
+    async fn reject_savepoints_of_other_snapshots(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?;
+        let mut t1 = store.current().await;
+        let mut t2 = store.current().await;
+        t1.insert(SLOT_0, "foo", 1)?;
+        t2.insert(SLOT_0, "foo", 2)?;
+        let sp1 = t1.savepoint();
+        let sp2 = t2.savepoint();
+        t1.insert(SLOT_0, "foo", 3)?;
+        t2.insert(SLOT_0, "foo", 4)?;
+
+        assert!(matches!(t2.rollback_to(sp1), Err(Error::ForeignSavepoint)));
+        assert!(matches!(t1.release(sp2), Err(Error::ForeignSavepoint)));
+        assert_eq!(t1.get(SLOT_0, &"foo").await?, Some(3));
+        assert_eq!(t2.get(SLOT_0, &"foo").await?, Some(4));
+
+        let outer = t1.savepoint();
+        let inner = t1.savepoint();
+        t1.rollback_to(outer)?;
+        let replacement = t1.savepoint();
+        assert!(matches!(t1.rollback_to(inner), Err(Error::UnknownSavepoint)));
+        t1.insert(SLOT_0, "foo", 5)?;
+        t1.rollback_to(replacement)?;
+        assert_eq!(t1.get(SLOT_0, &"foo").await?, Some(3));
+        t1.abort().await?;
+        t2.abort().await?;
+    }
+}
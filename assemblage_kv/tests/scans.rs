@@ -0,0 +1,99 @@
+use assemblage_kv::{test, KvStore, Result};
+use std::ops::Bound;
+
+#[cfg(target_arch = "wasm32")]
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+const SLOT_0: u8 = 0;
+const SLOT_1: u8 = 1;
+
+test! {
+    async fn range_of_int_keys(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        for i in 0..10 {
+            t.insert(SLOT_0, i, i * 10)?;
+            t.insert(SLOT_1, i, i * 100)?;
+        }
+        t.remove(SLOT_0, 4)?;
+        t.commit().await?;
+
+        let current = store.current().await;
+        let all = current.range::<u32, u32, _>(SLOT_0, ..).await?;
+        assert_eq!(all.len(), 9);
+        assert_eq!(all[0], (0, 0));
+        assert_eq!(all[8], (9, 90));
+
+        let entries = current.range::<u32, u32, _>(SLOT_0, 3..6).await?;
+        assert_eq!(entries, vec![(3, 30), (5, 50)]);
+
+        let entries = current.range::<u32, u32, _>(SLOT_1, 3..=5).await?;
+        assert_eq!(entries, vec![(3, 300), (4, 400), (5, 500)]);
+
+        let entries = current.range::<u32, u32, _>(SLOT_1, 8..).await?;
+        assert_eq!(entries, vec![(8, 800), (9, 900)]);
+
+        let entries = current.range::<u32, u32, _>(SLOT_1, (Bound::Excluded(2), Bound::Included(4))).await?;
+        assert_eq!(entries, vec![(3, 300), (4, 400)]);
+
+        let entries = current.range::<u32, u32, _>(SLOT_1, (Bound::Excluded(6), Bound::Excluded(2))).await?;
+        assert_eq!(entries, vec![]);
+    }
+}
+
+test! {
+    async fn range_respects_snapshot_isolation(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "b", 1)?;
+        t.insert(SLOT_0, "d", 2)?;
+        t.commit().await?;
+
+        let snapshot = store.current().await;
+
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "c", 3)?;
+        t.remove(SLOT_0, "d")?;
+        assert_eq!(
+            t.range::<String, u32, _>(SLOT_0, "a".to_string()..).await?,
+            vec![("b".to_string(), 1), ("c".to_string(), 3)]
+        );
+        t.commit().await?;
+
+        assert_eq!(
+            snapshot.range::<String, u32, _>(SLOT_0, "a".to_string()..).await?,
+            vec![("b".to_string(), 1), ("d".to_string(), 2)]
+        );
+
+        let current = store.current().await;
+        assert_eq!(
+            current.range::<String, u32, _>(SLOT_0, "a".to_string()..).await?,
+            vec![("b".to_string(), 1), ("c".to_string(), 3)]
+        );
+    }
+}
+
+test! {
+    async fn scan_prefix_of_tuple_keys(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, (1, "foo"), "1 foo")?;
+        t.insert(SLOT_0, (1, "bar"), "1 bar")?;
+        t.insert(SLOT_0, (2, "foo"), "2 foo")?;
+        t.insert(SLOT_1, (1, "baz"), "1 baz")?;
+        t.commit().await?;
+
+        let current = store.current().await;
+        let entries = current.scan_prefix::<(u8, String), String>(SLOT_0, &[0x92, 0x01]).await?;
+        assert_eq!(
+            entries,
+            vec![
+                ((1, "bar".to_string()), "1 bar".to_string()),
+                ((1, "foo".to_string()), "1 foo".to_string()),
+            ]
+        );
+
+        let entries = current.scan_prefix::<(u8, String), String>(SLOT_1, &[]).await?;
+        assert_eq!(entries, vec![((1, "baz".to_string()), "1 baz".to_string())]);
+    }
+}
@@ -0,0 +1,205 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+use assemblage_kv::{
+    hint_name,
+    storage::{SegmentOptions, SegmentedStorage, Storage},
+    test, CompactionPolicy, KvStore, MergePolicy, Result,
+};
+use std::{fs, path::Path};
+
+const SLOT_0: u8 = 0;
+
+/// Returns bytes that cannot be compressed, so that the segments have the same
+/// size with and without compression.
+fn noise(seed: u32, len: usize) -> Vec<u8> {
+    let mut x = seed.wrapping_mul(2654435761) | 1;
+    (0..len)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            x as u8
+        })
+        .collect()
+}
+
+test! {
+    async fn roll_over_and_merge_segments() -> Result<()> {
+        let dir = Path::new("segmented_storage_merge_dir");
+        fs::create_dir_all(dir).unwrap();
+        let options = SegmentOptions {
+            segment_size: 100,
+            ..SegmentOptions::default()
+        };
+        let storage = SegmentedStorage::open_at(dir, "store", options.clone()).await?;
+        let store = KvStore::open(storage).await?;
+        for i in 0..10u32 {
+            let mut t = store.current().await;
+            t.insert(SLOT_0, i, noise(i, 50))?;
+            t.commit().await?;
+        }
+        drop(store);
+
+        let mut storage = SegmentedStorage::open_at(dir, "store", options.clone()).await?;
+        let ranges = storage.segment_ranges();
+        assert!(ranges.len() > 2);
+        assert_eq!(ranges.last().unwrap().end, storage.len());
+        let (id, offset) = storage.locate(ranges[2].start + 1).unwrap();
+        assert_eq!((id, offset), (3, 1));
+        assert_eq!(storage.read(ranges[1].end - 2, 4).await?.len(), 4);
+
+        let mut store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        for i in 0..9u32 {
+            t.remove(SLOT_0, i)?;
+        }
+        t.commit().await?;
+        store.merge().await?;
+        assert_eq!(store.current().await.get(SLOT_0, &9).await?, Some(noise(9, 50)));
+        assert_eq!(store.current().await.keys::<u32>(SLOT_0).await?, vec![9]);
+        drop(store);
+        assert!(!dir.join("store.000001.seg").exists());
+
+        let storage = SegmentedStorage::open_at(dir, "store", options).await?;
+        assert_eq!(storage.segment_ranges().len(), 1);
+        let store = KvStore::open(storage).await?;
+        assert_eq!(store.current().await.get(SLOT_0, &9).await?, Some(noise(9, 50)));
+
+        SegmentedStorage::purge_at(dir, "store").await?;
+        SegmentedStorage::purge_at(dir, hint_name("store")).await?;
+        fs::remove_dir(dir).unwrap();
+    }
+}
+
+test! {
+    async fn compact_only_segments_with_garbage() -> Result<()> {
+        let dir = Path::new("segmented_storage_compact_dir");
+        fs::create_dir_all(dir).unwrap();
+        let options = SegmentOptions {
+            segment_size: 150,
+            ..SegmentOptions::default()
+        };
+        let storage = SegmentedStorage::open_at(dir, "store", options.clone()).await?;
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "removed", noise(0, 150))?;
+        t.commit().await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "kept", noise(1, 150))?;
+        t.commit().await?;
+        for i in 0..20u32 {
+            let mut t = store.current().await;
+            t.insert(SLOT_0, "updated", i)?;
+            t.commit().await?;
+        }
+        let mut t = store.current().await;
+        t.remove(SLOT_0, "removed")?;
+        t.commit().await?;
+        let ranges = store.into_storage()?.segment_ranges();
+
+        let storage = SegmentedStorage::open_at(dir, "store", options.clone()).await?;
+        let mut store = KvStore::open(storage).await?;
+        let len = store.len().await;
+        store.compact().await?;
+        assert!(store.len().await < len);
+        let current = store.current().await;
+        assert_eq!(current.get(SLOT_0, &"kept").await?, Some(noise(1, 150)));
+        assert_eq!(current.get(SLOT_0, &"updated").await?, Some(19));
+        // versions in the last segment are never compacted:
+        let versions = current.versions(SLOT_0, &"updated").await?.len();
+        assert!(versions < 20);
+        assert_eq!(current.get::<_, Vec<u8>>(SLOT_0, &"removed").await?, None);
+        drop(current);
+
+        let storage = store.into_storage()?;
+        let compacted = storage.segment_ranges();
+        assert_eq!(compacted.len(), ranges.len());
+        assert!(compacted[0].end < ranges[0].end);
+        assert_eq!(compacted[1].end - compacted[1].start, ranges[1].end - ranges[1].start);
+        assert!(compacted.last().unwrap().end < ranges.last().unwrap().end);
+        storage.purge_sibling(&hint_name("store")).await?;
+        let mut store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "updated", 20)?;
+        t.commit().await?;
+        let current = store.current().await;
+        assert_eq!(current.get(SLOT_0, &"kept").await?, Some(noise(1, 150)));
+        assert_eq!(current.get(SLOT_0, &"updated").await?, Some(20));
+        assert_eq!(current.versions(SLOT_0, &"updated").await?.len(), versions + 1);
+        assert_eq!(current.get::<_, Vec<u8>>(SLOT_0, &"removed").await?, None);
+        drop(current);
+
+        let policy = CompactionPolicy {
+            min_garbage_ratio: 0.0,
+            ..CompactionPolicy::default()
+        };
+        store.compact_with(policy).await?;
+        assert_eq!(store.current().await.keys::<String>(SLOT_0).await?, vec!["kept", "updated"]);
+
+        SegmentedStorage::purge_at(dir, "store").await?;
+        SegmentedStorage::purge_at(dir, hint_name("store")).await?;
+        fs::remove_dir(dir).unwrap();
+    }
+}
+
+test! {
+    async fn purge_removed_keys_during_compaction() -> Result<()> {
+        let dir = Path::new("segmented_storage_purge_removed_dir");
+        fs::create_dir_all(dir).unwrap();
+        let options = SegmentOptions {
+            segment_size: 150,
+            ..SegmentOptions::default()
+        };
+        let storage = SegmentedStorage::open_at(dir, "store", options.clone()).await?;
+        let mut store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "removed", noise(0, 150))?;
+        t.insert(SLOT_0, "trash", noise(1, 150))?;
+        t.commit().await?;
+        let mut t = store.current().await;
+        t.remove(SLOT_0, "removed")?;
+        t.remove(SLOT_0, "trash")?;
+        t.commit().await?;
+        for i in 0..3u32 {
+            let mut t = store.current().await;
+            t.insert(SLOT_0, i, noise(i + 2, 150))?;
+            t.commit().await?;
+        }
+
+        // the removed value is kept in the trash, so the removal is kept:
+        let policy = CompactionPolicy {
+            min_garbage_ratio: 0.0,
+            merge: MergePolicy {
+                keep_trash_for: Some(u64::MAX),
+                ..MergePolicy::default()
+            },
+        };
+        store.compact_with(policy).await?;
+        let current = store.current().await;
+        assert_eq!(current.versions(SLOT_0, &"removed").await?.len(), 2);
+        drop(current);
+
+        let policy = CompactionPolicy {
+            min_garbage_ratio: 0.0,
+            ..CompactionPolicy::default()
+        };
+        store.compact_with(policy).await?;
+        let current = store.current().await;
+        assert_eq!(current.versions(SLOT_0, &"removed").await?.len(), 0);
+        assert_eq!(current.get_unremoved::<_, Vec<u8>>(SLOT_0, &"trash").await?, None);
+        assert_eq!(current.keys::<u32>(SLOT_0).await?, vec![0, 1, 2]);
+        drop(current);
+
+        let storage = store.into_storage()?;
+        storage.purge_sibling(&hint_name("store")).await?;
+        let store = KvStore::open(storage).await?;
+        let current = store.current().await;
+        assert_eq!(current.versions(SLOT_0, &"removed").await?.len(), 0);
+        assert_eq!(current.versions(SLOT_0, &"trash").await?.len(), 0);
+        assert_eq!(current.get(SLOT_0, &2).await?, Some(noise(4, 150)));
+
+        SegmentedStorage::purge_at(dir, "store").await?;
+        SegmentedStorage::purge_at(dir, hint_name("store")).await?;
+        fs::remove_dir(dir).unwrap();
+    }
+}
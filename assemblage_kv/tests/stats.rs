@@ -0,0 +1,70 @@
+use assemblage_kv::{test, Error, KvStore, Result, SlotStats};
+
+#[cfg(target_arch = "wasm32")]
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+const SLOT_0: u8 = 0;
+const SLOT_1: u8 = 1;
+
+test! {
+    async fn report_store_stats(storage) -> Result<()> {
+        let mut store = KvStore::open(storage).await?;
+        let stats = store.stats().await?;
+        assert_eq!(stats.total_bytes, 0);
+        assert_eq!(stats.garbage_ratio(), 0.0);
+        assert_eq!(stats.cache_hit_rate(), None);
+
+        for i in 0..3 {
+            let mut t = store.current().await;
+            t.insert(SLOT_0, "foo", vec![i; 100])?;
+            t.insert(SLOT_1, "bar", i)?;
+            t.insert(SLOT_1, "baz", i)?;
+            t.commit().await?;
+        }
+        let mut t = store.current().await;
+        t.remove(SLOT_1, "baz")?;
+        t.commit().await?;
+
+        let t = store.current().await;
+        assert_eq!(t.get(SLOT_0, &"foo").await?, Some(vec![2; 100]));
+        assert_eq!(t.get(SLOT_0, &"foo").await?, Some(vec![2; 100]));
+        let mut t1 = store.current().await;
+        let mut t2 = store.current().await;
+        assert_eq!(t1.get(SLOT_1, &"bar").await?, Some(2));
+        t1.insert(SLOT_1, "bar", 3)?;
+        assert_eq!(t2.get(SLOT_1, &"bar").await?, Some(2));
+        t2.insert(SLOT_1, "bar", 4)?;
+        t1.commit().await?;
+        assert!(matches!(t2.commit().await, Err(Error::TransactionConflict)));
+
+        let stats = store.stats().await?;
+        assert_eq!(stats.total_bytes, store.len().await);
+        assert!(stats.live_bytes < stats.total_bytes / 2);
+        assert!(stats.garbage_ratio() > 0.5);
+        assert_eq!(stats.commits, 5);
+        assert_eq!(stats.conflicts, 1);
+        assert_eq!(stats.cache_hits, 2);
+        assert_eq!(stats.cache_misses, 2);
+        assert_eq!(stats.cache_hit_rate(), Some(0.5));
+        let slot_0 = SlotStats {
+            keys: 1,
+            trashed_keys: 0,
+            versions: 3,
+        };
+        let slot_1 = SlotStats {
+            keys: 1,
+            trashed_keys: 1,
+            versions: 8,
+        };
+        assert_eq!(stats.slots.get(&SLOT_0), Some(&slot_0));
+        assert_eq!(stats.slots.get(&SLOT_1), Some(&slot_1));
+
+        drop(t);
+        store.merge().await?;
+        let merged = store.stats().await?;
+        assert_eq!(merged.live_bytes, stats.live_bytes);
+        assert!(merged.total_bytes < stats.total_bytes);
+        assert!(merged.garbage_ratio() < stats.garbage_ratio());
+        assert_eq!(merged.slots.get(&SLOT_1).map(|s| s.versions), Some(2));
+    }
+}
@@ -74,6 +74,47 @@ test! {
     }
 }
 
+test! {
+    async fn pause_and_resume_merge(s) -> Result<()> {
+        s.write(&[0, 1, 2]).await?;
+
+        s.start_merge().await?;
+        s.write(&[3, 4]).await?;
+        s.pause_merge().await?;
+        s.write(&[5, 6, 7]).await?;
+        assert_eq!(s.len(), 6);
+        assert_eq!(s.read(3, 3).await?, vec![5, 6, 7]);
+        s.resume_merge().await?;
+        s.write(&[8]).await?;
+        s.stop_merge().await?;
+
+        assert_eq!(s.len(), 3);
+        assert_eq!(s.read(0, 3).await?, vec![3, 4, 8]);
+    }
+}
+
+test! {
+    async fn abort_merge(s) -> Result<()> {
+        s.write(&[0, 1, 2]).await?;
+
+        s.start_merge().await?;
+        s.write(&[3, 4]).await?;
+        s.pause_merge().await?;
+        s.write(&[5, 6]).await?;
+        s.abort_merge().await?;
+        s.write(&[7]).await?;
+
+        assert_eq!(s.len(), 6);
+        assert_eq!(s.read(0, 6).await?, vec![0, 1, 2, 5, 6, 7]);
+
+        s.start_merge().await?;
+        s.write(&[8]).await?;
+        s.stop_merge().await?;
+        assert_eq!(s.len(), 1);
+        assert_eq!(s.read(0, 1).await?, vec![8]);
+    }
+}
+
 test! {
     async fn read_beyond_end_of_storage(s) -> Result<()> {
         let buf = s.read(0, 10).await?;
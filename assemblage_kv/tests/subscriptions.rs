@@ -0,0 +1,67 @@
+use assemblage_kv::{test, KvStore, Result};
+use futures::StreamExt;
+
+#[cfg(target_arch = "wasm32")]
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+const SLOT_0: u8 = 0;
+const SLOT_1: u8 = 1;
+
+test! {
+    async fn receive_committed_changes(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?;
+        let mut subscription = store.subscribe().await;
+
+        let mut t = store.current().await;
+        t.insert(SLOT_1, "bar", 1)?;
+        t.insert(SLOT_0, "foo", 1)?;
+        t.commit().await?;
+
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "aborted", 1)?;
+        t.abort().await?;
+
+        let mut t = store.current().await;
+        t.remove(SLOT_0, "foo")?;
+        t.commit().await?;
+
+        let commit = subscription.recv().await.unwrap();
+        assert_eq!(Some(commit.timestamp), store.current().await.versions(SLOT_1, &"bar").await?.last().map(|v| v.timestamp));
+        assert_eq!(commit.changes.len(), 2);
+        assert_eq!((commit.changes[0].slot, commit.changes[0].is_removed), (SLOT_0, false));
+        assert_eq!(commit.changes[0].deserialize_key::<String>()?, "foo");
+        assert_eq!((commit.changes[1].slot, commit.changes[1].is_removed), (SLOT_1, false));
+        assert_eq!(commit.changes[1].deserialize_key::<String>()?, "bar");
+
+        let commit = subscription.next().await.unwrap();
+        assert_eq!(commit.changes.len(), 1);
+        assert!(commit.changes[0].is_removed);
+        assert_eq!(commit.changes[0].deserialize_key::<String>()?, "foo");
+
+        drop(store);
+        assert_eq!(subscription.recv().await, None);
+    }
+}
+
+test! {
+    async fn receive_only_changes_of_subscribed_slots(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?;
+        let mut subscription = store.subscribe_to(&[SLOT_1]).await;
+        let dropped_subscription = store.subscribe().await;
+        drop(dropped_subscription);
+
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 1)?;
+        t.commit().await?;
+
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 2)?;
+        t.insert(SLOT_1, "bar", 2)?;
+        t.commit().await?;
+
+        let commit = subscription.recv().await.unwrap();
+        assert_eq!(commit.changes.len(), 1);
+        assert_eq!(commit.changes[0].slot, SLOT_1);
+        assert_eq!(commit.changes[0].deserialize_key::<String>()?, "bar");
+    }
+}
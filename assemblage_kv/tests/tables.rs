@@ -0,0 +1,73 @@
+use assemblage_kv::{
+    table::{Table, TableRegistry},
+    test, Error, KvStore, Result,
+};
+
+#[cfg(target_arch = "wasm32")]
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+const NAMES: Table<u32, String> = Table::new("names", 0);
+const AGES: Table<String, u8> = Table::new("ages", 1);
+const ALIASES: Table<String, String> = Table::new("aliases", 1);
+
+test! {
+    async fn read_and_write_typed_tables(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        NAMES.insert(&mut t, &1, "foo".to_string())?;
+        NAMES.insert(&mut t, &2, "bar".to_string())?;
+        AGES.insert(&mut t, &"foo".to_string(), 30)?;
+        t.commit().await?;
+
+        let mut t = store.current().await;
+        NAMES.remove(&mut t, &2)?;
+        AGES.insert(&mut t, &"foo".to_string(), 31)?;
+        t.commit().await?;
+
+        let current = store.current().await;
+        assert_eq!(NAMES.keys(&current).await?, vec![1]);
+        assert_eq!(NAMES.get(&current, &2).await?, None);
+        assert_eq!(NAMES.get_unremoved(&current, &2).await?, Some("bar".to_string()));
+        assert_eq!(NAMES.range(&current, 0..10).await?, vec![(1, "foo".to_string())]);
+        let versions = AGES.versions(&current, &"foo".to_string()).await?;
+        assert_eq!(versions.len(), 2);
+        assert_eq!(AGES.get_version(&current, &"foo".to_string(), versions[0]).await?, Some(30));
+        assert_eq!(AGES.get(&current, &"foo".to_string()).await?, Some(31));
+        assert_eq!(current.get(AGES.slot(), &"foo").await?, Some(31));
+    }
+}
+
+test! {
+    async fn reject_tables_with_duplicate_slots() -> Result<()> {
+        let mut tables = TableRegistry::new();
+        tables.register(&NAMES)?;
+        tables.register(&AGES)?;
+        tables.register(&AGES)?;
+        assert_eq!(tables.get(1), Some("ages"));
+        match tables.register(&ALIASES) {
+            Err(Error::DuplicateSlot { slot, registered, rejected }) => {
+                assert_eq!(slot, 1);
+                assert_eq!(registered, "ages");
+                assert_eq!(rejected, "aliases");
+            }
+            result => panic!("expected a duplicate slot error, found {:?}", result),
+        }
+    }
+}
+
+test! {
+    async fn reject_tables_with_conflicting_types_or_slots() -> Result<()> {
+        const NAMES_BY_STRING: Table<String, String> = Table::new("names", 0);
+        const NAMES_IN_SLOT_2: Table<u32, String> = Table::new("names", 2);
+        let mut tables = TableRegistry::new();
+        tables.register(&NAMES)?;
+        for result in [tables.register(&NAMES_BY_STRING), tables.register(&NAMES_IN_SLOT_2)] {
+            match result {
+                Err(Error::ConflictingTable { name, .. }) => assert_eq!(name, "names"),
+                result => panic!("expected a conflicting table error, found {:?}", result),
+            }
+        }
+        assert_eq!(tables.get(0), Some("names"));
+        assert_eq!(tables.get(2), None);
+    }
+}
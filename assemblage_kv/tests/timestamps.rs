@@ -226,10 +226,11 @@ test! {
 
         sleep(100).await;
 
+        // commits never share a timestamp, even if the clock is behind:
         let t_after_transaction = timestamp_now_monotonic(0);
         let current = store.current().await;
         assert!(current.last_updated().await?.unwrap() > t_after_transaction);
-        assert_eq!(current.last_updated().await?.unwrap(), now_plus_10_minutes);
+        assert_eq!(current.last_updated().await?.unwrap(), now_plus_10_minutes + 1);
 
         let storage = storage::open(&store_name).await?;
         let mut store = KvStore::open(storage).await?;
@@ -237,7 +238,7 @@ test! {
         let mut t = store.current().await;
         assert_eq!(t.get::<_, Vec<u8>>(SLOT_0, &[5]).await?.unwrap(), vec![8]);
         assert!(t.last_updated().await?.unwrap() > timestamp_now_monotonic(0));
-        assert_eq!(t.last_updated().await?.unwrap(), now_plus_10_minutes);
+        assert_eq!(t.last_updated().await?.unwrap(), now_plus_10_minutes + 1);
         t.insert(SLOT_0, vec![5], vec![9])?;
         t.commit().await?;
 
@@ -250,7 +251,7 @@ test! {
         let t = store.current().await;
         assert_eq!(t.get::<_, Vec<u8>>(SLOT_0, &[5]).await?.unwrap(), vec![9]);
         assert!(t.last_updated().await?.unwrap() > timestamp_now_monotonic(0));
-        assert_eq!(t.last_updated().await?.unwrap(), now_plus_10_minutes);
+        assert_eq!(t.last_updated().await?.unwrap(), now_plus_10_minutes + 2);
         t.commit().await?;
     }
 }
@@ -300,6 +301,61 @@ test! {
     }
 }
 
+test! {
+    async fn read_only_snapshots_at_past_timestamps(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?;
+        let t0 = timestamp_now_monotonic(0);
+        sleep(10).await;
+
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 1)?;
+        t.insert(SLOT_0, "bar", 1)?;
+        t.commit().await?;
+        let t1 = store.current().await.last_updated().await?.unwrap();
+
+        sleep(10).await;
+
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 2)?;
+        t.remove(SLOT_0, "bar")?;
+        t.insert(SLOT_0, "baz", 2)?;
+        t.commit().await?;
+        let t2 = store.current().await.last_updated().await?.unwrap();
+        assert!(t1 < t2);
+
+        let past = store.at(t0).await;
+        assert_eq!(past.last_updated().await?, None);
+        assert_eq!(past.get::<_, u32>(SLOT_0, &"foo").await?, None);
+        assert_eq!(past.keys::<String>(SLOT_0).await?, Vec::<String>::new());
+
+        for timestamp in [t1, t2 - 1].iter() {
+            let past = store.at(*timestamp).await;
+            assert_eq!(past.last_updated().await?, Some(t1));
+            assert_eq!(past.get(SLOT_0, &"foo").await?, Some(1));
+            assert_eq!(past.get(SLOT_0, &"bar").await?, Some(1));
+            assert_eq!(past.get::<_, u32>(SLOT_0, &"baz").await?, None);
+            assert_eq!(past.versions(SLOT_0, &"foo").await?.len(), 1);
+            assert_eq!(past.keys::<String>(SLOT_0).await?, vec!["bar", "foo"]);
+        }
+
+        let mut past = store.at(t2).await;
+        assert_eq!(past.last_updated().await?, Some(t2));
+        assert_eq!(past.get(SLOT_0, &"foo").await?, Some(2));
+        assert_eq!(past.get::<_, u32>(SLOT_0, &"bar").await?, None);
+        assert_eq!(past.versions(SLOT_0, &"foo").await?.len(), 2);
+        assert_eq!(past.keys::<String>(SLOT_0).await?, vec!["baz", "foo"]);
+
+        match past.insert(SLOT_0, "foo", 3) {
+            Err(Error::ReadOnlySnapshot) => {}
+            instead => panic!("Expected a read-only error, but found {:?}", instead),
+        }
+        match past.commit().await {
+            Err(Error::ReadOnlySnapshot) => {}
+            instead => panic!("Expected a read-only error, but found {:?}", instead),
+        }
+    }
+}
+
 #[cfg(target_arch = "wasm32")]
 async fn sleep(millis: u64) {
     let promise = js_sys::Promise::new(&mut |yes, _| {
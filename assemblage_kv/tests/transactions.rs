@@ -1,4 +1,6 @@
-use assemblage_kv::{storage, storage::Storage, test, Error, KvStore, Result};
+use assemblage_kv::{
+    storage, storage::Storage, test, Error, KvStore, RepairStrategy, Result, RetryPolicy,
+};
 
 #[cfg(target_arch = "wasm32")]
 wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
@@ -537,6 +539,164 @@ test! {
     }
 }
 
+test! {
+    async fn open_read_only_without_repairing_corrupt_data(storage) -> Result<()> {
+        let store_name = String::from(storage.name());
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 1)?;
+        t.commit().await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 2)?;
+        t.commit().await?;
+        let len = store.len().await;
+        drop(store);
+
+        let storage = storage::open(&store_name).await?;
+        let mut store = KvStore::open_read_only(storage).await?;
+        assert!(store.is_read_only());
+        let mut t = store.current().await;
+        assert_eq!(t.get(SLOT_0, &"foo").await?, Some(2));
+        assert!(matches!(t.insert(SLOT_0, "foo", 3), Err(Error::ReadOnlyStore)));
+        assert!(matches!(t.commit().await, Err(Error::ReadOnlyStore)));
+        assert!(matches!(store.merge().await, Err(Error::ReadOnlyStore)));
+        assert!(matches!(store.write_hint().await, Err(Error::ReadOnlyStore)));
+        assert_eq!(store.len().await, len);
+
+        corrupt_last_bytes(store.into_storage()?, 1).await?;
+        let storage = storage::open(&store_name).await?;
+        let result = KvStore::open_read_only(storage).await;
+        assert!(matches!(result, Err(Error::CorruptDataError(_))));
+
+        let storage = storage::open(&store_name).await?;
+        assert_eq!(storage.len(), len);
+        let store = KvStore::open(storage).await?;
+        assert_eq!(store.current().await.get(SLOT_0, &"foo").await?, Some(1));
+        assert!(store.len().await < len);
+    }
+}
+
+test! {
+    async fn commit_after_truncating_corrupt_transaction(storage) -> Result<()> {
+        let store_name = String::from(storage.name());
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 1)?;
+        t.commit().await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 2)?;
+        t.commit().await?;
+        corrupt_last_bytes(store.into_storage()?, 1).await?;
+
+        let storage = storage::open(&store_name).await?;
+        let store = KvStore::open(storage).await?;
+        assert_eq!(store.current().await.get(SLOT_0, &"foo").await?, Some(1));
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 3)?;
+        t.commit().await?;
+        drop(store);
+
+        let storage = storage::open(&store_name).await?;
+        let store = KvStore::open(storage).await?;
+        assert_eq!(store.current().await.get(SLOT_0, &"foo").await?, Some(3));
+    }
+}
+
+test! {
+    async fn verify_and_salvage_transactions_after_corruption(storage) -> Result<()> {
+        let store_name = String::from(storage.name());
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 1)?;
+        t.commit().await?;
+        let len_first = store.len().await;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 2)?;
+        t.insert(SLOT_0, "bar", 2)?;
+        t.commit().await?;
+        let len_second = store.len().await;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "baz", 3)?;
+        t.commit().await?;
+        let len_third = store.len().await;
+        let mut storage = store.into_storage()?;
+
+        assert!(KvStore::verify(&mut storage).await?.is_ok());
+        let bytes_commit = 1 + 1 + 6 + 4;
+        let third = storage.read(len_second, (len_third - len_second) as u32).await?;
+        storage.write(&third[..third.len() - bytes_commit]).await?;
+        let len = storage.len();
+        corrupt_byte_at(&mut storage, len_second - 1).await?;
+
+        let report = KvStore::verify(&mut storage).await?;
+        assert!(!report.is_ok());
+        assert_eq!(report.commits, 2);
+        assert_eq!(report.entries, 4);
+        assert_eq!(report.corrupt_offsets, vec![len_first]);
+        assert_eq!(report.orphaned_entries, 1);
+        assert_eq!(report.bytes_dropped, len - len_first - (len_third - len_second));
+        assert_eq!(report.bytes_truncated, len - len_first);
+
+        assert_eq!(KvStore::repair(&mut storage, RepairStrategy::Salvage).await?, report);
+        let repaired = KvStore::verify(&mut storage).await?;
+        assert!(repaired.is_ok());
+        assert_eq!(repaired.commits, 2);
+        drop(storage);
+
+        let storage = storage::open(&store_name).await?;
+        let store = KvStore::open(storage).await?;
+        let current = store.current().await;
+        assert_eq!(current.get(SLOT_0, &"foo").await?, Some(1));
+        assert_eq!(current.get::<_, u32>(SLOT_0, &"bar").await?, None);
+        assert_eq!(current.get(SLOT_0, &"baz").await?, Some(3));
+    }
+}
+
+test! {
+    async fn salvage_transactions_after_corrupt_entry_sizes(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "foo", 1)?;
+        t.commit().await?;
+        let len_first = store.len().await;
+        let mut t = store.current().await;
+        for i in 0..200u32 {
+            t.insert(SLOT_0, i, i)?;
+        }
+        t.commit().await?;
+        let len_second = store.len().await;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "baz", 3)?;
+        t.commit().await?;
+        let mut storage = store.into_storage()?;
+
+        // all entries after the corrupt header lead to a commit with the wrong
+        // CRC, but the transaction after it must still be found:
+        corrupt_byte_at(&mut storage, len_first).await?;
+        let report = KvStore::verify(&mut storage).await?;
+        assert_eq!(report.commits, 2);
+        assert_eq!(report.corrupt_offsets, vec![len_first]);
+        assert_eq!(report.bytes_dropped, len_second - len_first);
+
+        KvStore::repair(&mut storage, RepairStrategy::Salvage).await?;
+        assert!(KvStore::verify(&mut storage).await?.is_ok());
+        let store = KvStore::open(storage).await?;
+        let current = store.current().await;
+        assert_eq!(current.get(SLOT_0, &"foo").await?, Some(1));
+        assert_eq!(current.get::<_, u32>(SLOT_0, &0).await?, None);
+        assert_eq!(current.get(SLOT_0, &"baz").await?, Some(3));
+    }
+}
+
+async fn corrupt_byte_at<S: Storage>(storage: &mut S, offset: u64) -> storage::Result<()> {
+    let len = storage.len();
+    let mut bytes = storage.read(offset, (len - offset) as u32).await?;
+    bytes[0] = !bytes[0];
+    storage.truncate(offset).await?;
+    storage.write(&bytes).await?;
+    Ok(())
+}
+
 async fn corrupt_last_bytes<S: Storage>(mut storage: S, bytes: u8) -> storage::Result<()> {
     let bytes = bytes as u64;
     let len = storage.len();
@@ -548,3 +708,62 @@ async fn corrupt_last_bytes<S: Storage>(mut storage: S, bytes: u8) -> storage::R
     storage.write(&corrupted).await?;
     Ok(())
 }
+
+test! {
+    async fn retry_conflicting_transaction(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?;
+        let mut t = store.current().await;
+        t.insert(SLOT_0, "count", 0)?;
+        t.commit().await?;
+
+        let store = &store;
+        let mut attempts = 0;
+        let count = store
+            .transact(|t| {
+                attempts += 1;
+                let is_first_attempt = attempts == 1;
+                Box::pin(async move {
+                    let count = t.get::<_, u32>(SLOT_0, &"count").await?.unwrap();
+                    if is_first_attempt {
+                        let mut concurrent = store.current().await;
+                        concurrent.insert(SLOT_0, "count", count + 10)?;
+                        concurrent.commit().await?;
+                    }
+                    t.insert(SLOT_0, "count", count + 1)?;
+                    Ok(count + 1)
+                })
+            })
+            .await?;
+        assert_eq!(attempts, 2);
+        assert_eq!(count, 11);
+        assert_eq!(store.current().await.get(SLOT_0, &"count").await?, Some(11));
+    }
+}
+
+test! {
+    async fn give_up_after_exhausting_retries(storage) -> Result<()> {
+        let store = KvStore::open(storage).await?;
+        let store = &store;
+        let policy = RetryPolicy {
+            max_retries: 2,
+            ..RetryPolicy::default()
+        };
+        let mut attempts = 0;
+        let result = store
+            .transact_with(policy, |t| {
+                attempts += 1;
+                Box::pin(async move {
+                    let count = t.get::<_, u32>(SLOT_0, &"count").await?.unwrap_or(0);
+                    let mut concurrent = store.current().await;
+                    concurrent.insert(SLOT_0, "count", count + 10)?;
+                    concurrent.commit().await?;
+                    t.insert(SLOT_0, "count", count + 1)?;
+                    Ok(())
+                })
+            })
+            .await;
+        assert!(matches!(result, Err(Error::TransactionConflict)));
+        assert_eq!(attempts, 3);
+        assert_eq!(store.current().await.get(SLOT_0, &"count").await?, Some(30));
+    }
+}